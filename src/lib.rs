@@ -1,57 +1,555 @@
 extern crate regex;
 extern crate fancy_regex;
 
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
 use regex::Regex;
 use fancy_regex::Regex as FancyRegex;
 
+mod matcher;
+mod router;
+pub mod patterns;
+
+pub use matcher::PathMatcher;
+pub use router::Router;
+
 /**
  * Default configs.
  */
 const DEFAULT_DELIMITER: char = '/';
 
+#[derive(Clone)]
 pub struct Options {
-    delimiter: char,
+    delimiter: String,
     whitelist: Vec<String>,
     strict: bool,
     sensitive: bool,
     end: bool,
     start: bool,
-    ends_with: Vec<String>
+    ends_with: Vec<String>,
+    segment_strict: bool,
+    keep_unnamed: bool,
+    lenient_start: bool,
+    boundary: bool,
+    absolute_end: bool,
+    multi_line: bool,
+    dot_all: bool,
+    decode: bool,
+    default_pattern: Option<String>,
+    fold: Option<Arc<dyn Fn(char) -> char + Send + Sync>>
 }
 impl Default for Options {
     fn default () -> Options {
         Options {
-            delimiter: DEFAULT_DELIMITER,
+            delimiter: DEFAULT_DELIMITER.to_string(),
             whitelist: Vec::new(),
             strict: false,
             sensitive: false,
             end: true,
             start: true,
-            ends_with: Vec::new()
+            ends_with: Vec::new(),
+            segment_strict: false,
+            keep_unnamed: true,
+            lenient_start: false,
+            boundary: false,
+            absolute_end: false,
+            multi_line: false,
+            dot_all: false,
+            decode: false,
+            default_pattern: None,
+            fold: None
+        }
+    }
+}
+impl Options {
+    pub fn builder () -> OptionsBuilder {
+        OptionsBuilder {
+            options: Options::default()
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/**
+ * Builder for `Options`, starting from the defaults.
+ */
+pub struct OptionsBuilder {
+    options: Options
+}
+impl OptionsBuilder {
+    /**
+     * Set the default delimiter of segments, `/` by default or when empty.
+     * It can have several characters, e.g. `::`.
+     */
+    pub fn delimiter<S> (mut self, delimiter: S) -> OptionsBuilder
+        where S: Into<String> {
+        let delimiter = delimiter.into();
+        self.options.delimiter = if delimiter.is_empty() {
+            DEFAULT_DELIMITER.to_string()
+        } else {
+            delimiter
+        };
+        self
+    }
+
+    /**
+     * Set the characters which can prefix a token, e.g. `["/", "-"]`.
+     * When empty, the default, the delimiter and `.` can.
+     */
+    pub fn whitelist<I> (mut self, whitelist: I) -> OptionsBuilder
+        where I: IntoIterator, I::Item: Into<String> {
+        self.options.whitelist = whitelist.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /**
+     * Set whether a trailing delimiter is not optional. Strict `/foo/:bar`
+     * matches `/foo/x` but not `/foo/x/`, or with `end` unset matches
+     * `/foo/x/` only up to the delimiter, leaving `/` unmatched.
+     */
+    pub fn strict (mut self, strict: bool) -> OptionsBuilder {
+        self.options.strict = strict;
+        self
+    }
+
+    /**
+     * Set whether matching is case sensitive. Otherwise the regexp folds
+     * the case of literals and patterns alike, Unicode letters included,
+     * so `/ÜBER` matches `/über`. Folding is one character to one, so
+     * `/Straße` matches `/STRAẞE` but not `/STRASSE`.
+     */
+    pub fn sensitive (mut self, sensitive: bool) -> OptionsBuilder {
+        self.options.sensitive = sensitive;
+        self
+    }
+
+    /**
+     * Set whether a match must reach the end of the text. Otherwise it
+     * may stop before a delimiter, so `/foo/:bar` matches the start of
+     * `/foo/x/y` but not of `/foo/xy` as `/foo/x`.
+     */
+    pub fn end (mut self, end: bool) -> OptionsBuilder {
+        self.options.end = end;
+        self
+    }
+
+    /**
+     * Set whether a match must begin at the start of the text.
+     */
+    pub fn start (mut self, start: bool) -> OptionsBuilder {
+        self.options.start = start;
+        self
+    }
+
+    /**
+     * Set whether tokens only match whole segments, so a custom pattern
+     * can not match part of one.
+     */
+    pub fn segment_strict (mut self, segment_strict: bool) -> OptionsBuilder {
+        self.options.segment_strict = segment_strict;
+        self
+    }
+
+    /**
+     * Set the strings which, besides the end of the text, end a match,
+     * e.g. `["?", "#"]` to stop at the query string or the fragment. They
+     * are matched literally and left out of the match, so `/users/:id`
+     * gives `5` for `/users/5?x=1` and `match_prefix` leaves `?x=1`.
+     */
+    pub fn ends_with<I> (mut self, ends_with: I) -> OptionsBuilder
+        where I: IntoIterator, I::Item: Into<String> {
+        self.options.ends_with = ends_with.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /**
+     * Set whether un-named groups like `(\\d+)` capture under a numeric
+     * name. Otherwise they are only used for matching.
+     */
+    pub fn keep_unnamed (mut self, keep_unnamed: bool) -> OptionsBuilder {
+        self.options.keep_unnamed = keep_unnamed;
+        self
+    }
+
+    /**
+     * Set whether a match tolerates a missing or extra leading delimiter,
+     * see `leading_delimiter` to find out which form was matched.
+     */
+    pub fn lenient_start (mut self, lenient_start: bool) -> OptionsBuilder {
+        self.options.lenient_start = lenient_start;
+        self
+    }
+
+    /**
+     * Set whether a match must be bounded by whitespace or the ends of the
+     * text instead of spanning all of it, to find paths within free text
+     * such as `GET /users/42 HTTP/1.1`. Word boundaries (`\\b`) are not used
+     * as there is none between a space and a leading `/`.
+     */
+    pub fn boundary (mut self, boundary: bool) -> OptionsBuilder {
+        self.options.boundary = boundary;
+        self
+    }
+
+    /**
+     * Set whether matches end at `\\z` instead of `$`. Both are the end of
+     * the text, but `$` also matches before every newline in multi-line
+     * mode, so `/foo\\n` could match `/foo`.
+     */
+    pub fn absolute_end (mut self, absolute_end: bool) -> OptionsBuilder {
+        self.options.absolute_end = absolute_end;
+        self
+    }
+
+    /**
+     * Set whether `^` and `$` also match at the start and end of every
+     * line, e.g. to find a route on any line of a text with `start` set.
+     */
+    pub fn multi_line (mut self, multi_line: bool) -> OptionsBuilder {
+        self.options.multi_line = multi_line;
+        self
+    }
+
+    /**
+     * Set whether `.` in custom patterns also matches newlines.
+     */
+    pub fn dot_all (mut self, dot_all: bool) -> OptionsBuilder {
+        self.options.dot_all = dot_all;
+        self
+    }
+
+    /**
+     * Set whether matched values are percent-decoded with
+     * `decode_path_segment`, the raw values staying available.
+     */
+    pub fn decode (mut self, decode: bool) -> OptionsBuilder {
+        self.options.decode = decode;
+        self
+    }
+
+    /**
+     * Set the pattern of tokens without a custom capture group, e.g. `\\w+`,
+     * instead of anything but the delimiter. Modifiers apply to it just as
+     * to a custom pattern, so `/:path+` repeats it.
+     */
+    pub fn default_pattern<S: Into<String>> (mut self, pattern: S) -> OptionsBuilder {
+        self.options.default_pattern = Some(pattern.into());
+        self
+    }
+
+    /**
+     * Set the case folding used to compare literal routes of a `Router`
+     * when `sensitive` is not set, e.g. to fold `İ` to `i` for Turkish.
+     * Only the literal fast path uses it, not regexp matching.
+     */
+    pub fn fold<F> (mut self, fold: F) -> OptionsBuilder
+        where F: Fn(char) -> char + Send + Sync + 'static {
+        self.options.fold = Some(Arc::new(fold));
+        self
+    }
+
+    pub fn build (self) -> Options {
+        self.options
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     name: String,
+    // Whether the name is the number of an un-named group.
+    unnamed: bool,
     prefix: String,
-    delimiter: char,
+    delimiter: String,
     optional: bool,
     repeat: bool,
-    pattern: String
+    // The least number of values a repeat token matches when present.
+    min_repeat: usize,
+    pattern: String,
+    // Whether values are percent-decoded when matched.
+    decode: bool
 }
+impl Token {
+    /**
+     * Get the name of the param, its index for an un-named group, or an
+     * empty string for a non-capturing group.
+     */
+    pub fn name (&self) -> &str {
+        self.name.as_str()
+    }
 
-#[derive(Debug)]
+    pub fn prefix (&self) -> &str {
+        self.prefix.as_str()
+    }
+
+    pub fn delimiter (&self) -> &str {
+        self.delimiter.as_str()
+    }
+
+    pub fn is_optional (&self) -> bool {
+        self.optional
+    }
+
+    pub fn is_repeat (&self) -> bool {
+        self.repeat
+    }
+
+    /**
+     * Get the least number of values a repeat token matches when present.
+     */
+    pub fn min_repeat (&self) -> usize {
+        self.min_repeat
+    }
+
+    /**
+     * Get the regexp matching a single value of the param.
+     */
+    pub fn pattern (&self) -> &str {
+        self.pattern.as_str()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub struct Match {
     name: String,
-    value: String
+    value: String,
+    raw_value: String,
+    values: Vec<String>,
+    start: usize,
+    end: usize
+}
+impl Match {
+    pub fn name (&self) -> &str {
+        self.name.as_str()
+    }
+
+    /**
+     * Get the captured value, for a repeated param the segments joined by
+     * their delimiter as in the text, e.g. `a/b/c`. It is percent-decoded
+     * when parsed with the `decode` option.
+     *
+     * Values are always sliced from the text as they are, so matching
+     * `/USER/JohnDoe` against `/user/:name` without `sensitive` still
+     * gives `JohnDoe`: only the comparison is case insensitive.
+     */
+    pub fn value (&self) -> &str {
+        self.value.as_str()
+    }
+
+    /**
+     * Get the value as it is in the text, not percent-decoded even with the
+     * `decode` option.
+     */
+    pub fn raw_value (&self) -> &str {
+        self.raw_value.as_str()
+    }
+
+    /**
+     * Get the segments of a repeated param, e.g. `["a", "b", "c"]`, or
+     * the single value of any other param. Segments are split at the
+     * delimiter of the token, which is its prefix when it has one rather
+     * than always `/`.
+     */
+    pub fn values (&self) -> &[String] {
+        self.values.as_slice()
+    }
+
+    /**
+     * Get the byte offset in the text where the value starts, so that
+     * `&text[m.start()..m.end()] == m.raw_value()`.
+     */
+    pub fn start (&self) -> usize {
+        self.start
+    }
+
+    /**
+     * Get the byte offset in the text where the value ends.
+     */
+    pub fn end (&self) -> usize {
+        self.end
+    }
+}
+
+#[derive(Debug)]
+pub struct DetailedMatch {
+    name: String,
+    value: String,
+    group: usize,
+    subs: Vec<Option<String>>,
+    leading_delim: Option<char>,
+    trailing_delim: Option<char>
+}
+impl DetailedMatch {
+    pub fn name (&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn value (&self) -> &str {
+        self.value.as_str()
+    }
+
+    pub fn group (&self) -> usize {
+        self.group
+    }
+
+    pub fn subs (&self) -> &[Option<String>] {
+        self.subs.as_slice()
+    }
+
+    pub fn leading_delim (&self) -> Option<char> {
+        self.leading_delim
+    }
+
+    pub fn trailing_delim (&self) -> Option<char> {
+        self.trailing_delim
+    }
+}
+
+/**
+ * A param matched with different values by two chained matches.
+ */
+#[derive(Debug)]
+pub struct Conflict {
+    name: String,
+    outer: String,
+    inner: String
+}
+impl Conflict {
+    pub fn name (&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn outer (&self) -> &str {
+        self.outer.as_str()
+    }
+
+    pub fn inner (&self) -> &str {
+        self.inner.as_str()
+    }
+}
+
+/**
+ * A regexp with a different number of capture groups than the containers
+ * it is matched with.
+ */
+#[derive(Debug)]
+pub struct CaptureMismatch {
+    expected: usize,
+    found: usize
+}
+impl CaptureMismatch {
+    pub fn expected (&self) -> usize {
+        self.expected
+    }
+
+    pub fn found (&self) -> usize {
+        self.found
+    }
+}
+
+/**
+ * Error turning a pattern into a regexp.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    // The generated regexp does not compile, e.g. because of an invalid
+    // custom pattern, with the regexp and the error of `fancy_regex`.
+    InvalidRegex {
+        regex: String,
+        message: String
+    },
+    // A parenthesis of the pattern, at this byte index, opens or closes no
+    // group, e.g. in `/:id(\\d+`, or groups are nested too deep.
+    UnbalancedGroup {
+        index: usize
+    },
+    // A parameter name is declared more than once, e.g. in `/:id/:id`,
+    // with the name.
+    DuplicateParameter(String),
+    // The param at this byte index directly follows a param with the
+    // default pattern, e.g. in `/:a:b`, with nothing to tell them apart.
+    MissingDelimiter {
+        index: usize
+    }
+}
+impl std::fmt::Display for PathError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathError::InvalidRegex { regex, message } => write!(f, "invalid regexp `{}`: {}", regex, message),
+            PathError::UnbalancedGroup { index } => write!(f, "unbalanced group at index {}", index),
+            PathError::DuplicateParameter(name) => write!(f, "duplicate parameter \"{}\"", name),
+            PathError::MissingDelimiter { index } => write!(f, "missing delimiter before the parameter at index {}", index)
+        }
+    }
+}
+impl std::error::Error for PathError {}
+
+/**
+ * Error building a path from containers and params with `compile`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+    // A required token has no value, with the name of the token.
+    MissingParameter(String),
+    // A value does not match the pattern of its token, e.g. `a/b` for
+    // `:id`, with the name of the token and the value.
+    InvalidParameterValue {
+        name: String,
+        value: String
+    },
+    // A required non-capturing group, e.g. `(?:foo|bar)`, has no name to
+    // take a value from, with the pattern of the group.
+    NonCapturingGroup(String)
+}
+impl std::fmt::Display for CompileError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::MissingParameter(name) => write!(f, "missing value for required parameter \"{}\"", name),
+            CompileError::InvalidParameterValue { name, value } => write!(f, "value \"{}\" does not match parameter \"{}\"", value, name),
+            CompileError::NonCapturingGroup(pattern) => write!(f, "no value for required non-capturing group \"{}\"", pattern)
+        }
+    }
+}
+impl std::error::Error for CompileError {}
+
+/**
+ * Form of the leading delimiter of a text compared to its pattern.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeadingDelimiter {
+    Missing,
+    Canonical,
+    Extra
 }
 
+/**
+ * A part of a parsed pattern: a literal path or a param.
+ */
 #[derive(Debug, Clone)]
-pub struct Container {
-    token: Option<Token>,
-    path: String
+pub enum Container {
+    // A literal part of the pattern, matched as it is.
+    Literal(String),
+    // A param, matched by the pattern of its token.
+    Param(Token)
+}
+impl Container {
+    /**
+     * Get the token, or `None` for a literal path.
+     */
+    pub fn token (&self) -> Option<&Token> {
+        match self {
+            Container::Literal(_) => None,
+            Container::Param(token) => Some(token)
+        }
+    }
+
+    /**
+     * Get the literal path, empty for a token.
+     */
+    pub fn path (&self) -> &str {
+        match self {
+            Container::Literal(path) => path.as_str(),
+            Container::Param(_) => ""
+        }
+    }
 }
 
 /**
@@ -61,46 +559,112 @@ pub struct Container {
  * @return {String}
  */
 fn escape_string (string: String) -> String {
-    let re = Regex::new(r"([.+*?=^!:${}()[\]|/\\]])").unwrap();
+    static RE: OnceLock<Regex> = OnceLock::new();
+
+    let re = RE.get_or_init(|| Regex::new(r"([.+*?=^!:${}()[\]|/\\]])").unwrap());
     re.replace_all(string.as_str(), r"\$1").into_owned()
 }
 
 /**
- * Escape the capturing group by escaping special characters and meaning.
+ * Escape the capturing group for the route regexp. Only `$` and `/` are
+ * escaped, so the group keeps its meaning and `(.*)` still matches across
+ * delimiters. Nested groups are kept, so they capture on their own.
  *
  * @param  {String} group
  * @return {String}
  */
 fn escape_group (group: String) -> String {
-    let re = Regex::new(r"([=!:$/()])").unwrap();
+    static RE: OnceLock<Regex> = OnceLock::new();
+
+    let re = RE.get_or_init(|| Regex::new(r"([$/])").unwrap());
     re.replace_all(group.as_str(), r"\$1").into_owned()
 }
 
+/**
+ * Reverse `escape_group` to get back the capturing group as it was written.
+ *
+ * @param  {&str} group
+ * @return {String}
+ */
+fn unescape_group (group: &str) -> String {
+    static RE: OnceLock<Regex> = OnceLock::new();
+
+    let re = RE.get_or_init(|| Regex::new(r"\\([$/])").unwrap());
+    re.replace_all(group, "$1").into_owned()
+}
+
 /**
  * Get the flags for a regexp from the options.
  *
- * @param  {Options} options
+ * @param  {&str} route
+ * @param  {&Options} options
  * @return {String}
  */
-fn flags (route: &str, options: Options) -> String {
+fn flags (route: &str, options: &Options) -> String {
+    let mut flags = String::new();
     if !options.sensitive {
-        format!("(?i){}", route)
+        flags.push('i');
+    }
+    if options.multi_line {
+        flags.push('m');
+    }
+    if options.dot_all {
+        flags.push('s');
+    }
+
+    if !flags.is_empty() {
+        format!("(?{}){}", flags, route)
     } else {
         String::from(route)
     }
 }
 
 /**
- * Parse a string for the raw tokens and paths.
+ * Get the pattern of a token without a custom capture group.
  *
- * @param  {&str} text
- * @param  {Options} options
- * @return (Vec<Container>)
+ * @param  {&str} delimiter
+ * @param  {&str} default_delimiter
+ * @return {String}
+ */
+fn default_pattern (delimiter: &str, default_delimiter: &str) -> String {
+    let delimiters = if delimiter == default_delimiter {
+        vec![delimiter]
+    } else {
+        vec![delimiter, default_delimiter]
+    };
+    // Only escape what is special in a class, `regex` rejects `\/` there.
+    let class: String = delimiters.iter()
+        .filter(|delimiter| delimiter.chars().count() == 1)
+        .flat_map(|delimiter| delimiter.chars())
+        .map(|c| match c {
+            '\\' | ']' | '[' | '^' | '-' => format!("\\{}", c),
+            _ => c.to_string()
+        })
+        .collect();
+    // Delimiters of several characters can only be excluded by lookaheads.
+    let lookaheads: String = delimiters.iter()
+        .filter(|delimiter| delimiter.chars().count() > 1)
+        .map(|delimiter| format!("(?!{})", escape_string(delimiter.to_string())))
+        .collect();
+
+    if lookaheads.is_empty() {
+        format!("[^{}]+?", class)
+    } else if class.is_empty() {
+        format!("(?:{}[\\s\\S])+?", lookaheads)
+    } else {
+        format!("(?:{}[^{}])+?", lookaheads, class)
+    }
+}
+
+/**
+ * Get the regexp tokenizing patterns, compiled once for the process.
+ *
+ * @return {&Regex}
  */
-pub fn parse (text: &str, options: Options) -> Vec<Container> {
-    let default_delimiter: char = options.delimiter;
-    let whitelist: &Vec<String> = &options.whitelist;
-    let path_regexp: Regex = Regex::new(vec![
+fn path_regexp () -> &'static Regex {
+    static PATH_REGEXP: OnceLock<Regex> = OnceLock::new();
+
+    PATH_REGEXP.get_or_init(|| Regex::new([
         // Match escaped characters that would otherwise appear in future matches.
         // This allows the user to escape special characters that won't transform.
         r"(\\.)",
@@ -109,24 +673,100 @@ pub fn parse (text: &str, options: Options) -> Vec<Container> {
         //
         // ":test(\\d+)?" => ["test", "\d+", NONE, "?"]
         // "(\\d+)"  => [NONE, NONE, "\d+", NONE]
-        r"(?::(\w+)(?:\(((?:\\.|[^\\()])+)\))?|\(((?:\\.|[^\\()])+)\))([+*?])?"
-    ].join("|").as_str()).unwrap();
+        //
+        // Patterns may contain one level of nested groups.
+        r"(?::(\w+)(?:\(((?:\\.|\((?:\\.|[^\\()])*\)|[^\\()])+)\))?|\(((?:\\.|\((?:\\.|[^\\()])*\)|[^\\()])+)\))([+*?])?",
+        // Match a param in braces with its explicit prefix, which the
+        // modifier applies to as well:
+        //
+        // "{/:id}?" => ["/", "id", NONE, NONE, "?"]
+        r"\{([^{}:()\\]*)(?::(\w+)(?:\(((?:\\.|\((?:\\.|[^\\()])*\)|[^\\()])+)\))?|\(((?:\\.|\((?:\\.|[^\\()])*\)|[^\\()])+)\))\}([+*?])?"
+    ].join("|").as_str()).unwrap())
+}
+
+/**
+ * Check that a literal part of a pattern, starting at `start` in it, has
+ * no parenthesis the tokenizer left out of a group.
+ *
+ * @param  {&str} literal
+ * @param  {usize} start
+ * @return {Result<(), PathError>}
+ */
+fn check_groups (literal: &str, start: usize) -> Result<(), PathError> {
+    match literal.find(['(', ')']) {
+        Some(index) => Err(PathError::UnbalancedGroup { index: start + index }),
+        None => Ok(())
+    }
+}
+
+/**
+ * Get the strings a param may take as its prefix, the delimiter and `.`
+ * unless the `whitelist` option is set.
+ *
+ * @param  {&Options} options
+ * @return {Vec<String>}
+ */
+fn prefix_whitelist (options: &Options) -> Vec<String> {
+    if options.whitelist.is_empty() {
+        vec![options.delimiter.clone(), String::from(".")]
+    } else {
+        options.whitelist.clone()
+    }
+}
+
+/**
+ * Get the pattern of tokens without a custom capture group for the given
+ * token delimiter, `default_pattern` when the option is set.
+ *
+ * @param  {&str} delimiter
+ * @param  {&Options} options
+ * @return {String}
+ */
+fn fallback_pattern (delimiter: &str, options: &Options) -> String {
+    match options.default_pattern {
+        Some(ref pattern) => pattern.clone(),
+        None => default_pattern(delimiter, options.delimiter.as_str())
+    }
+}
+
+/**
+ * Parse a string for the raw tokens and paths. Fails when a group of the
+ * pattern is not terminated, instead of matching it literally, when a
+ * param name is declared twice, or when a param directly follows one with
+ * the default pattern.
+ *
+ * Un-named groups are named by their number among the un-named groups of
+ * the pattern, from `0`. A param named like one of these numbers is a
+ * duplicate of it.
+ *
+ * A param can be wrapped in braces with an explicit prefix, which its
+ * modifier applies to, e.g. `/users{/:id}?` or `/file{-v:version}*`.
+ *
+ * Any character preceded by a backslash is literal, so `/\\:id` matches
+ * `/:id` and `/\\(a\\)` matches `/(a)`. An escaped character is never
+ * the prefix of a following param.
+ *
+ * @param  {&str} text
+ * @param  {Options} options
+ * @return {Result<Vec<Container>, PathError>}
+ */
+pub fn parse (text: &str, options: Options) -> Result<Vec<Container>, PathError> {
+    let default_delimiter: &str = options.delimiter.as_str();
+    let whitelist = prefix_whitelist(&options);
+    let path_regexp: &Regex = path_regexp();
     let mut index = 0;
     let mut key = -1;
     let mut path = String::new();
     let mut path_escaped = false;
     let mut containers: Vec<Container> = vec![];
 
-    fn unwrap_match_to_str (m: Option<regex::Match>) -> &str {
-        if m != None {
-            m.unwrap().as_str()
-        } else {
-            ""
-        }
-    }
+    let fallback_pattern = |delimiter: &str| fallback_pattern(delimiter, &options);
 
-    if !path_regexp.is_match(text) {
-        return containers;
+    fn unwrap_match_to_str (m: Option<regex::Match<'_>>) -> &str {
+        match m {
+            Some(m) => m.as_str(),
+            None => ""
+        }
     }
 
     for res in path_regexp.captures_iter(text) {
@@ -134,153 +774,348 @@ pub fn parse (text: &str, options: Options) -> Vec<Container> {
         let escaped = res.get(1);
         let offset = m.start();
 
-        path.push_str(text.get(index..offset).unwrap());
+        check_groups(&text[index..offset], index)?;
+        path.push_str(&text[index..offset]);
         index = offset + m.as_str().len();
 
-        // Ignore already escaped sequences.
-        if escaped != None {
-            path.push_str(escaped.unwrap().as_str());
+        // Keep the character of escaped sequences, without the backslash.
+        if let Some(escaped) = escaped {
+            path.push_str(&escaped.as_str()[1..]);
             path_escaped = true;
             continue;
         }
 
-        let mut prev: String = String::new();
-        let name = unwrap_match_to_str(res.get(2));
-        let capture = unwrap_match_to_str(res.get(3));
-        let group = res.get(4);
-        let modifier = unwrap_match_to_str(res.get(5));
+        // With a delimiter ending in `:` like `::`, a `:` completing it is
+        // literal, so `foo::bar` is not `foo:` followed by the param `bar`.
+        if res.get(2).is_some() && default_delimiter.ends_with(':') {
+            let before = &text[..offset];
+            if !before.ends_with(default_delimiter) && format!("{}:", before).ends_with(default_delimiter) {
+                path.push_str(m.as_str());
+                continue;
+            }
+        }
+
+        // The groups of a param in braces follow its explicit prefix.
+        let brace_prefix = res.get(6);
+        let base = if brace_prefix.is_some() { 5 } else { 0 };
+        let mut prev: String = String::from(unwrap_match_to_str(brace_prefix));
+        let name = unwrap_match_to_str(res.get(base + 2));
+        let capture = unwrap_match_to_str(res.get(base + 3));
+        let group = res.get(base + 4);
+        let modifier = unwrap_match_to_str(res.get(base + 5));
 
-        if !path_escaped && path.len() > 0 {
-            let k = path.len();
-            let c = String::from(path.get(k-1..k).unwrap());
-            let matches: bool = if whitelist.len() > 0 {
-                whitelist.into_iter().find(|&x| x == &c) != None
-            } else {
-                false
-            };
+        // The end of the path may be the prefix: the longest entry of the
+        // whitelist it ends with, or without a whitelist the delimiter or `.`.
+        if !path_escaped && brace_prefix.is_none() {
+            let candidate: Option<String> = whitelist.iter()
+                .filter(|x| !x.is_empty() && path.ends_with(x.as_str()))
+                .max_by_key(|x| x.len())
+                .cloned();
 
-            if matches {
+            if let Some(c) = candidate {
+                path.truncate(path.len() - c.len());
                 prev = c;
-                path = String::from(path.get(0..k).unwrap());
             }
         }
 
         // Push the current path onto the tokens.
-        if path != "" {
-            containers.push(Container {
-                path,
-                token: None
-            });
+        if !path.is_empty() {
+            containers.push(Container::Literal(path));
             path = String::new();
             path_escaped = false;
         }
 
+        // A param right after one with the default pattern, as in `/:a:b`,
+        // leaves the value of the first one to chance.
+        if let (true, Some(Container::Param(last))) = (prev.is_empty(), containers.last()) {
+            if last.pattern == fallback_pattern(&last.delimiter) {
+                return Err(PathError::MissingDelimiter { index: offset });
+            }
+        }
+
         let repeat = modifier == "+" || modifier == "*";
         let optional = modifier == "?" || modifier == "*";
-        let pattern = if capture.len() > 0 {
+        let mut pattern = if !capture.is_empty() {
             capture
-        } else if group != None {
-            group.unwrap().as_str()
+        } else if let Some(group) = group {
+            group.as_str()
         } else {
             ""
         };
-        let delimiter: char = if prev != "" {
-            prev.chars().next().unwrap()
+        // Un-named groups written as `(?:...)`, or all of them unless
+        // `keep_unnamed` is set, are non-capturing and left without a name.
+        let capturing = !name.is_empty() || (options.keep_unnamed && !pattern.starts_with("?:"));
+        if name.is_empty() && pattern.starts_with("?:") {
+            pattern = &pattern[2..];
+        }
+        let delimiter: String = if !prev.is_empty() {
+            prev.clone()
         } else {
-            default_delimiter
+            String::from(default_delimiter)
         };
 
-        containers.push(Container {
-            path: String::new(),
-            token: Some(Token {
-                name: if name != "" {
-                    name.to_owned()
-                } else {
-                    key += 1;
-                    key.to_string()
-                },
-                prefix: prev,
-                delimiter: delimiter,
-                optional: optional,
-                repeat: repeat,
-                pattern: if pattern != "" {
-                    escape_group(pattern.to_owned())
-                } else {
-                    let pattern_delimiter = if delimiter == default_delimiter {
-                        delimiter.to_string()
-                    } else {
-                        vec![delimiter, default_delimiter].into_iter().collect()
-                    };
-                    
-                    format!(r"[^\{}]+?", escape_string(pattern_delimiter))
-                }
-            })
-        });
+        let unnamed = name.is_empty() && capturing;
+        let name = if !name.is_empty() {
+            name.to_owned()
+        } else if !capturing {
+            String::new()
+        } else {
+            key += 1;
+            key.to_string()
+        };
+        // Numbered groups share the names of params, so `/:0/(\\d+)` is
+        // a duplicate too.
+        if !name.is_empty() && containers.iter().any(|c| c.token().is_some_and(|t| t.name == name)) {
+            return Err(PathError::DuplicateParameter(name));
+        }
+
+        containers.push(Container::Param(Token {
+            name,
+            unnamed,
+            prefix: prev,
+            optional,
+            repeat,
+            min_repeat: 1,
+            pattern: if !pattern.is_empty() {
+                escape_group(pattern.to_owned())
+            } else {
+                fallback_pattern(delimiter.as_str())
+            },
+            decode: options.decode,
+            delimiter
+        }));
     }
 
     // Push any remaining characters.
-    if path.len() > 0 || index < text.len() {
-        path.push_str(text.get(index..text.len()).unwrap());
-        containers.push(Container {
-            path,
-            token: None
-        });
+    if !path.is_empty() || index < text.len() {
+        check_groups(&text[index..], index)?;
+        path.push_str(&text[index..]);
+        containers.push(Container::Literal(path));
     }
 
-    containers
+    Ok(containers)
 }
 
 /**
- * Expose a function for taking containers and returning a FancyRegex.
+ * Get the names of the params of a pattern in order, with the numbers of
+ * un-named groups, e.g. `["id", "0"]` for `/users/:id/(.*)`. Fails like
+ * `parse`.
  *
- * @param  {Vec<Container>} containers
- * @param  {Options} options
- * @return {FancyRegex}
+ * @param  {&str} text
+ * @param  {&Options} options
+ * @return {Result<Vec<String>, PathError>}
  */
-pub fn to_regexp (containers: &Vec<Container>, options: Options) -> FancyRegex {
-    let strict = options.strict;
-    let start = options.start;
-    let end = options.end;
-    let delimiter = options.delimiter;
-    let ends_with = if options.ends_with.len() > 0 {
-        let mut _ends_with_vec = &options.ends_with;
-        let mut _ends_with: Vec<String> = _ends_with_vec.into_iter().map(|s| {
-            escape_string(s.to_string())
-        }).collect();
-        _ends_with.push(String::from("$"));
-        _ends_with.join("|")
+pub fn parse_names (text: &str, options: &Options) -> Result<Vec<String>, PathError> {
+    let containers = parse(text, options.clone())?;
+
+    Ok(containers.into_iter()
+        .filter_map(|container| match container {
+            Container::Literal(_) => None,
+            Container::Param(token) => Some(token)
+        })
+        .filter(|token| !token.name.is_empty())
+        .map(|token| token.name)
+        .collect())
+}
+
+/**
+ * Get the regexp matching the value of a token, repeated if needed. The
+ * value of a required token must start with a character even when its
+ * pattern matches an empty text, like `(.*)`, so `/:x(.*)` does not match
+ * `/`. A pattern matching an empty text before another character, like
+ * `(a*)` in `/:x(a*)b`, still captures an empty value there.
+ *
+ * @param  {&Token} token
+ * @return {String}
+ */
+fn token_capture (token: &Token) -> String {
+    let capture = if token.repeat {
+        let quantifier = if token.min_repeat > 1 {
+            format!("{{{},}}", token.min_repeat - 1)
+        } else {
+            String::from("*")
+        };
+
+        format!("(?:{})(?:{}(?:{})){}", token.pattern.as_str(), escape_string(token.delimiter.clone()).as_str(), token.pattern.as_str(), quantifier)
     } else {
-        String::from("$")
+        token.pattern.clone()
     };
-    let mut route = if start == true {
-        String::from("^")
+
+    if !token.optional && matches_empty(token.pattern.as_str()) {
+        format!("(?=[\\s\\S])(?:{})", capture)
     } else {
-        String::from("")
-    };
+        capture
+    }
+}
 
-    // Iterate over the containers and create our regexp string.
-    for i in 0..containers.len() {
-        let container = &containers[i];
-        
-        if !container.path.is_empty() {
-            route.push_str(escape_string(container.path.to_string()).as_str());
-        } else {
-            let token = container.token.as_ref().unwrap();
-            let prefix = String::from(token.prefix.as_str());
-            let capture = if token.repeat == true {
-                format!("(?:{})(?:{}(?:{}))*", token.pattern.as_str(), escape_string(token.delimiter.to_string()).as_str(), token.pattern.as_str())
-            } else {
-                format!("{}", token.pattern.as_str())
-            };
+/**
+ * Tell whether a pattern matches the empty text. A pattern which does not
+ * compile matches nothing.
+ *
+ * @param  {&str} pattern
+ * @return {bool}
+ */
+fn matches_empty (pattern: &str) -> bool {
+    FancyRegex::new(format!("^(?:{})$", pattern).as_str())
+        .ok()
+        .and_then(|regexp| regexp.is_match("").ok())
+        .unwrap_or(false)
+}
 
-            if token.optional {
-                if token.prefix != "" {
-                    route.push_str(format!("({})", capture.as_str()).as_str());
-                } else {
-                    route.push_str(format!("(?:{}({}))?", escape_string(prefix).as_str(), capture.as_str()).as_str());
+/**
+ * Build a lookahead for any of the alternatives. Every alternative gets its
+ * own lookahead, as `fancy_regex` mismatches alternations inside one.
+ *
+ * @param  {&[String]} alternatives
+ * @return {String}
+ */
+fn lookahead (alternatives: &[String]) -> String {
+    let lookaheads: Vec<String> = alternatives.iter().map(|a| format!("(?={})", a)).collect();
+
+    if lookaheads.len() == 1 {
+        lookaheads.join("")
+    } else {
+        format!("(?:{})", lookaheads.join("|"))
+    }
+}
+
+/**
+ * Expose a function for taking containers and returning a FancyRegex.
+ * Fails when the generated regexp does not compile, e.g. because a custom
+ * pattern of a token is invalid.
+ *
+ * @param  {&[Container]} containers
+ * @param  {Options} options
+ * @return {Result<FancyRegex, PathError>}
+ */
+pub fn to_regexp (containers: &[Container], options: Options) -> Result<FancyRegex, PathError> {
+    compile_regexp(to_regexp_string(containers, &options))
+}
+
+/**
+ * Get the source of the regexp `to_regexp` builds from the containers,
+ * without compiling it, e.g. `(?i)^\/users\/([^/]+?)(?:\/)?$` for
+ * `/users/:id`, to log it or compare it with other implementations.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&Options} options
+ * @return {String}
+ */
+pub fn to_regexp_string (containers: &[Container], options: &Options) -> String {
+    flags(route_regexp(containers, options).as_str(), options)
+}
+
+/**
+ * Build the regexp of the containers like `to_regexp`, with the `regex`
+ * crate, which matches in linear time. Fails when the regexp needs
+ * lookarounds, as with `end` unset or `ends_with`, a required param
+ * with a pattern matching an empty value like `(.*)`, or a custom pattern
+ * using them, so callers can fall back to `to_regexp`.
+ *
+ * @param  {&[Container]} containers
+ * @param  {Options} options
+ * @return {Result<Regex, PathError>}
+ */
+pub fn to_regexp_fast (containers: &[Container], options: Options) -> Result<Regex, PathError> {
+    let regex_str = to_regexp_string(containers, &options);
+
+    Regex::new(regex_str.as_str()).map_err(|err| PathError::InvalidRegex {
+        message: err.to_string(),
+        regex: regex_str
+    })
+}
+
+/**
+ * Compile a regexp string, keeping it in the error when it is invalid.
+ *
+ * @param  {String} regex_str
+ * @return {Result<FancyRegex, PathError>}
+ */
+fn compile_regexp (regex_str: String) -> Result<FancyRegex, PathError> {
+    FancyRegex::new(regex_str.as_str()).map_err(|err| PathError::InvalidRegex {
+        message: format!("{:?}", err),
+        regex: regex_str
+    })
+}
+
+/**
+ * Build the regexp string of the containers, without the flags.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&Options} options
+ * @return {String}
+ */
+fn route_regexp (containers: &[Container], options: &Options) -> String {
+    let strict = options.strict;
+    let start = options.start;
+    let end = options.end;
+    let delimiter = options.delimiter.as_str();
+    let mut ends_with: Vec<String> = options.ends_with.iter().map(|s| {
+        escape_string(s.to_string())
+    }).collect();
+    if options.boundary {
+        ends_with.push(String::from(r"\s"));
+    }
+    let end_anchor = if options.absolute_end { r"\z" } else { "$" };
+    ends_with.push(String::from(end_anchor));
+    let mut route = if options.boundary {
+        String::from(r"(?:^|(?<=\s))")
+    } else if start {
+        String::from("^")
+    } else {
+        String::from("")
+    };
+    // Accept any number of leading delimiters in place of the one (or
+    // none) at the start of the pattern.
+    let mut lenient_start = start && options.lenient_start;
+    if lenient_start {
+        route.push_str(format!("(?:{})*", escape_string(delimiter.to_string())).as_str());
+    }
+
+    // Iterate over the containers and create our regexp string.
+    for container in containers {
+        match container {
+            Container::Literal(path) => {
+                let mut path = path.as_str();
+                if lenient_start {
+                    path = path.strip_prefix(delimiter).unwrap_or(path);
+                }
+                lenient_start = false;
+
+                route.push_str(escape_string(path.to_string()).as_str());
+            },
+            Container::Param(token) => {
+                let mut prefix = String::from(token.prefix.as_str());
+                if lenient_start && !token.optional && prefix.starts_with(delimiter) {
+                    prefix.drain(..delimiter.len());
+                }
+                lenient_start = false;
+                let capture = token_capture(token);
+                // Make sure the capture spans whole segments only.
+                let capture = if options.segment_strict {
+                    let mut boundaries = vec![escape_string(delimiter.to_string())];
+                    if token.delimiter != delimiter {
+                        boundaries.push(escape_string(token.delimiter.clone()));
+                    }
+                    let behind: Vec<String> = boundaries.iter().map(|b| format!("(?<={})", b)).collect();
+                    let ahead = lookahead(&[boundaries, ends_with.clone()].concat());
+
+                    format!("(?:^|{})(?:{}){}", behind.join("|"), capture, ahead)
+                } else {
+                    capture
+                };
+
+                let group = if token.name.is_empty() {
+                    "?:"
+                } else {
+                    ""
+                };
+
+                if token.optional {
+                    route.push_str(format!("(?:{}({}{}))?", escape_string(prefix).as_str(), group, capture.as_str()).as_str());
+                } else {
+                    route.push_str(format!("{}({}{})", escape_string(prefix).as_str(), group, capture.as_str()).as_str());
                 }
-            } else {
-                route.push_str(format!("{}({})", escape_string(prefix).as_str(), capture.as_str()).as_str());
             }
         }
     }
@@ -290,70 +1125,3204 @@ pub fn to_regexp (containers: &Vec<Container>, options: Options) -> FancyRegex {
             route.push_str(format!("(?:{})?", escape_string(delimiter.to_string())).as_str());
         }
 
-        if ends_with == "$" {
-            route.push_str("$");
+        if ends_with.len() == 1 {
+            route.push_str(end_anchor);
         } else {
-            route.push_str(format!("(?={})", ends_with).as_str());
+            route.push_str(lookahead(&ends_with).as_str());
         };
     } else {
-        let last_container: &Container = containers.last().unwrap();
-        let is_end_delimited = if !last_container.path.is_empty() {
-            let last_path_char = last_container.path.get(last_container.path.len() - 1..last_container.path.len()).unwrap();
-            last_path_char.to_string()
+        // A pattern ending in a literal delimiter, or an empty one, may be
+        // followed by anything. Otherwise the match stops before a
+        // delimiter, so `/api` matches `/api/users` but not `/apiv2`.
+        let is_end_delimited = containers.last()
+            .map_or(true, |container| container.path().ends_with(delimiter));
+
+        if !strict {
+            route.push_str(format!("(?:{}{})?", escape_string(delimiter.to_string()), lookahead(&ends_with)).as_str());
+        }
+
+        if !is_end_delimited {
+            route.push_str(lookahead(&[vec![escape_string(delimiter.to_string())], ends_with].concat()).as_str());
+        }
+    }
+
+    route
+}
+
+/**
+ * Compile several patterns into one regexp matching any of them, e.g.
+ * `["/users/:id", "/accounts/:id"]`, with the containers of all of them,
+ * so `match_str` gives the params of the pattern which matched. The first
+ * pattern matching the text wins.
+ *
+ * The containers join the patterns into one, so they are only meant for
+ * matching with the regexp: `compile` would substitute every pattern.
+ *
+ * @param  {&[&str]} patterns
+ * @param  {Options} options
+ * @return {Result<(FancyRegex, Vec<Container>), PathError>}
+ */
+pub fn array_to_regexp (patterns: &[&str], options: Options) -> Result<(FancyRegex, Vec<Container>), PathError> {
+    let mut containers: Vec<Container> = vec![];
+    let mut routes: Vec<String> = vec![];
+
+    // Groups are numbered across the alternatives, so the groups of every
+    // pattern follow those of the patterns before it like its containers.
+    for pattern in patterns {
+        let pattern_containers = parse(pattern, options.clone())?;
+        routes.push(format!("(?:{})", route_regexp(&pattern_containers, &options)));
+        containers.extend(pattern_containers);
+    }
+
+    let route = routes.join("|");
+    let regexp = compile_regexp(flags(route.as_str(), &options))?;
+
+    Ok((regexp, containers))
+}
+
+/**
+ * Compile a raw regexp string, with containers naming its capturing
+ * groups `0`, `1`, ... in order, so `match_str` can label their values as
+ * it does for un-named groups of a pattern. A regexp without capturing
+ * groups gives no containers.
+ *
+ * The groups are optional, as any of them may capture nothing in a raw
+ * regexp, and `(?:...)` or lookaround groups are not counted.
+ *
+ * Unlike `regexpToRegexp` of path-to-regexp, it takes the source of the
+ * regexp rather than a compiled one, as a `FancyRegex` does not give its
+ * source back, and it returns the tokens as containers like `parse`, to
+ * use with `match_str` directly.
+ *
+ * @param  {&str} path
+ * @return {Result<(FancyRegex, Vec<Container>), PathError>}
+ */
+pub fn regexp_to_regexp (path: &str) -> Result<(FancyRegex, Vec<Container>), PathError> {
+    let regexp = compile_regexp(String::from(path))?;
+    let containers = (0..count_groups(path))
+        .map(|key| Container::Param(Token {
+            name: key.to_string(),
+            unnamed: true,
+            prefix: String::new(),
+            delimiter: DEFAULT_DELIMITER.to_string(),
+            optional: true,
+            repeat: false,
+            min_repeat: 1,
+            pattern: String::new(),
+            decode: false
+        }))
+        .collect();
+
+    Ok((regexp, containers))
+}
+
+/**
+ * Count the capturing groups of a regexp string.
+ *
+ * @param  {&str} pattern
+ * @return {usize}
+ */
+fn count_groups (pattern: &str) -> usize {
+    let mut count = 0;
+    let mut chars = pattern.chars().peekable();
+    let mut class = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            },
+            '[' if !class => {
+                class = true;
+                // A leading `]` is part of the class.
+                if chars.peek() == Some(&'^') {
+                    chars.next();
+                }
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                }
+            },
+            ']' if class => class = false,
+            '(' if !class => {
+                // Named groups capture too, unlike `(?:`, `(?=` or `(?<=`.
+                let group: String = chars.clone().take(3).collect();
+                let named = group.starts_with("?P<")
+                    || (group.starts_with("?<") && !group.starts_with("?<=") && !group.starts_with("?<!"));
+
+                if !group.starts_with('?') || named {
+                    count += 1;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    count
+}
+
+struct TokenCapture<'a> {
+    group: usize,
+    token: &'a Token,
+    value: String,
+    start: usize,
+    end: usize,
+    subs: Vec<Option<String>>
+}
+impl<'a> TokenCapture<'a> {
+    fn into_match (self) -> Match {
+        let decode = |value: &str| if self.token.decode {
+            decode_path_segment(value)
         } else {
-            String::new()
+            String::from(value)
+        };
+        // Split before decoding, so encoded delimiters stay in their value.
+        let values = if self.token.repeat && self.value.is_empty() {
+            vec![]
+        } else if self.token.repeat {
+            self.value.split(self.token.delimiter.as_str()).map(decode).collect()
+        } else {
+            vec![decode(self.value.as_str())]
         };
 
-        if !strict {
-            route.push_str(format!("(?:{}(?={}))?", escape_string(delimiter.to_string()), ends_with).as_str());
+        Match {
+            name: String::from(self.token.name.as_str()),
+            value: decode(self.value.as_str()),
+            raw_value: self.value,
+            values,
+            start: self.start,
+            end: self.end
+        }
+    }
+}
+
+/**
+ * Map the capture groups of a match onto the tokens of the containers.
+ * Each token has its own group, followed by the groups nested in its
+ * pattern. Tokens whose group did not participate and non-capturing
+ * tokens are left out.
+ *
+ * The groups are found by index, as `fancy_regex` does not support named
+ * groups (`(?P<name>...)`), but the index of every token is computed from
+ * the groups before it, so optional groups which did not participate do
+ * not shift the names of the following tokens.
+ *
+ * A required token does not match an empty value at the end of the text,
+ * see `token_capture`. The regexp alone decides, so the captures always
+ * agree with `is_match`.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Option<Vec<TokenCapture>>}
+ */
+fn token_captures<'a> (text: &str, regexp: &FancyRegex, containers: &'a [Container]) -> Option<Vec<TokenCapture<'a>>> {
+    let caps = captures_from(regexp, text, 0)?;
+
+    Some(captures_to_tokens(text, &caps, containers))
+}
+
+/**
+ * Map the capture groups of a match onto the tokens of the containers,
+ * see `token_captures`.
+ *
+ * @param  {&str} text
+ * @param  {&fancy_regex::Captures} caps
+ * @param  {&[Container]} containers
+ * @return {Vec<TokenCapture>}
+ */
+fn captures_to_tokens<'a> (text: &str, caps: &fancy_regex::Captures, containers: &'a [Container]) -> Vec<TokenCapture<'a>> {
+    let mut captures: Vec<TokenCapture> = vec![];
+    let mut group = 1;
+
+    for token in containers.iter().filter_map(|container| container.token()) {
+        let nested = count_groups(token_capture(token).as_str());
+
+        // Non-capturing groups have no group of their own.
+        if token.name.is_empty() {
+            group += nested;
+            continue;
         }
 
-        if is_end_delimited.is_empty() {
-            route.push_str(format!("(?={}|{})", escape_string(delimiter.to_string()), ends_with).as_str());
+        if let Some((start, end)) = caps.pos(group) {
+            captures.push(TokenCapture {
+                group,
+                token,
+                value: text[start..end].to_owned(),
+                start,
+                end,
+                subs: (group + 1..=group + nested).map(|i| caps.at(i).map(String::from)).collect()
+            });
         }
+
+        group += 1 + nested;
     }
 
-    let regex_str = format!(r"{}", flags(route.as_str(), options).as_str());
+    captures
+}
+
+/**
+ * Tell whether the regexp matches the text. The only error `fancy_regex`
+ * reports while matching is a stack overflow of its backtracking, which
+ * only a pathological pattern can cause, so it is taken as no match.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @return {bool}
+ */
+pub fn is_match (text: &str, regexp: &FancyRegex) -> bool {
+    try_is_match(text, regexp).unwrap_or(false)
+}
+
+/**
+ * Tell whether the regexp matches the text, reporting the errors of the
+ * backtracking engine instead of treating them as no match.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @return {Result<bool, fancy_regex::Error>}
+ */
+pub fn try_is_match (text: &str, regexp: &FancyRegex) -> Result<bool, fancy_regex::Error> {
+    regexp.is_match(text)
+}
 
-    FancyRegex::new(regex_str.as_str()).unwrap()
+/**
+ * Get the captures of the regexp matching the text from `pos`, taking an
+ * error of the backtracking engine as no match like `is_match`.
+ *
+ * @param  {&FancyRegex} regexp
+ * @param  {&str} text
+ * @param  {usize} pos
+ * @return {Option<fancy_regex::Captures>}
+ */
+fn captures_from<'t> (regexp: &FancyRegex, text: &'t str, pos: usize) -> Option<fancy_regex::Captures<'t>> {
+    regexp.captures_from_pos(text, pos).ok().flatten()
 }
 
 /**
- * Function for matching text with parsed tokens.
+ * Function for matching text with parsed tokens. Like for `is_match`, an
+ * error of the backtracking engine gives no matches instead of a panic.
  *
  * @param  {&str} text
- * @param  {FancyRegex} regexp
- * @param  {Vec<Container>} containers
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
  * @return {Vec<Match>}
  */
-pub fn match_str (text: &str, regexp: FancyRegex, containers: Vec<Container>) -> Vec<Match> {
+pub fn match_str (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Vec<Match> {
+    token_captures(text, regexp, containers).unwrap_or_default().into_iter()
+        .map(TokenCapture::into_match)
+        .collect()
+}
+
+/**
+ * Match text and return the values keyed by the name of their token, with
+ * un-named groups under their index as in `parse`. Returns `None` when the
+ * text does not match. The value of a repeat token is the raw capture.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Option<HashMap<String, String>>}
+ */
+pub fn match_map (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Option<HashMap<String, String>> {
+    let captures = token_captures(text, regexp, containers)?;
+
+    Some(captures.into_iter()
+        .map(TokenCapture::into_match)
+        .map(|m| (m.name, m.value))
+        .collect())
+}
+
+/**
+ * Match text and return the value of the named param only, like the value
+ * of its `Match`. Returns `None` when the text does not match or the param
+ * is optional and absent.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @param  {&str} name
+ * @return {Option<String>}
+ */
+pub fn match_param (text: &str, regexp: &FancyRegex, containers: &[Container], name: &str) -> Option<String> {
+    let (group, token) = token_group(containers, name)?;
+    let caps = captures_from(regexp, text, 0)?;
+    let (start, end) = caps.pos(group)?;
+    let value = &text[start..end];
+
+    if token.decode {
+        Some(decode_path_segment(value))
+    } else {
+        Some(String::from(value))
+    }
+}
+
+/**
+ * Get the index of the capture group of the named token, counted like in
+ * `captures_to_tokens`, with the token.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&str} name
+ * @return {Option<(usize, &Token)>}
+ */
+fn token_group<'a> (containers: &'a [Container], name: &str) -> Option<(usize, &'a Token)> {
+    let mut group = 1;
+
+    for token in containers.iter().filter_map(|container| container.token()) {
+        let nested = count_groups(token_capture(token).as_str());
+
+        // Non-capturing groups have no group of their own.
+        if token.name.is_empty() {
+            group += nested;
+            continue;
+        }
+
+        if token.name == name {
+            return Some((group, token));
+        }
+        group += 1 + nested;
+    }
+
+    None
+}
+
+/**
+ * Find every non-overlapping match in the text, each given as its matches,
+ * e.g. to scan log lines for embedded paths with a regexp built with
+ * `start` and `end` unset, or with `boundary`. The search continues after
+ * the end of each match.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {impl Iterator<Item = Vec<Match>>}
+ */
+pub fn match_iter<'a> (text: &'a str, regexp: &'a FancyRegex, containers: &'a [Container]) -> impl Iterator<Item = Vec<Match>> + 'a {
+    let mut pos = 0;
+
+    std::iter::from_fn(move || {
+        if pos > text.len() {
+            return None;
+        }
+
+        let caps = captures_from(regexp, text, pos)?;
+        let (start, end) = caps.pos(0)?;
+        // Step over empty matches so the search always advances.
+        pos = if start == end {
+            end + text[end..].chars().next().map_or(1, char::len_utf8)
+        } else {
+            end
+        };
+
+        Some(captures_to_tokens(text, &caps, containers).into_iter().map(TokenCapture::into_match).collect())
+    })
+}
+
+/**
+ * Match the start of text, e.g. with a regexp built with `end` unset to
+ * mount a sub-router, and return the matches with the rest of the text
+ * after the match, like `/users` for `/api` and `/api/users`. Returns
+ * `None` when the text does not match.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Option<(Vec<Match>, String)>}
+ */
+pub fn match_prefix (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Option<(Vec<Match>, String)> {
+    let (_, end) = regexp.find(text).ok().flatten()?;
+    let captures = token_captures(text, regexp, containers)?;
+    let matches = captures.into_iter().map(TokenCapture::into_match).collect();
+
+    Some((matches, String::from(&text[end..])))
+}
+
+/**
+ * Match the start of text like `match_prefix`, returning the byte offset
+ * the match ends at instead of the rest, e.g. 6 for `/admin` and
+ * `/admin/users/3` to hand `/users/3` to a mounted matcher. Only a regexp
+ * built with `end` unset may stop before the end of the text.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Option<(Vec<Match>, usize)>}
+ */
+pub fn match_consumed (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Option<(Vec<Match>, usize)> {
+    let caps = captures_from(regexp, text, 0)?;
+    let (_, end) = caps.pos(0)?;
+    let captures = captures_to_tokens(text, &caps, containers);
+
+    Some((captures.into_iter().map(TokenCapture::into_match).collect(), end))
+}
+
+/**
+ * Find the first match anywhere in text, e.g. with a regexp built with
+ * `start` and `end` unset to extract a path from free text, and return
+ * its byte span with the matches. Returns `None` when nothing matches.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Option<(usize, usize, Vec<Match>)>}
+ */
+pub fn search (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Option<(usize, usize, Vec<Match>)> {
+    let caps = captures_from(regexp, text, 0)?;
+    let (start, end) = caps.pos(0)?;
+    let captures = captures_to_tokens(text, &caps, containers);
+
+    Some((start, end, captures.into_iter().map(TokenCapture::into_match).collect()))
+}
+
+/**
+ * Match text like `match_str`, after checking that the regexp has as many
+ * capture groups as `to_regexp` generates for the containers. Passing a
+ * regexp built from other containers is an error instead of silently
+ * returning values under the wrong names.
+ *
+ * The groups are only known once the regexp matched, so a text that does
+ * not match gives no matches rather than an error.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Result<Vec<Match>, CaptureMismatch>}
+ */
+pub fn match_checked (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Result<Vec<Match>, CaptureMismatch> {
+    if let Some(caps) = captures_from(regexp, text, 0) {
+        let expected = capture_count(containers);
+        let found = caps.len() - 1;
+
+        if found != expected {
+            return Err(CaptureMismatch {
+                expected,
+                found
+            });
+        }
+    }
+
+    Ok(token_captures(text, regexp, containers).unwrap_or_default().into_iter()
+        .map(TokenCapture::into_match)
+        .collect())
+}
+
+/**
+ * Match text like `match_str`, additionally recording the regexp capture
+ * group each value was taken from and the values of the groups nested in
+ * the param pattern, e.g. `3` and `4` for `/at/:coord((\\d+),(\\d+))`
+ * matching `/at/3,4`.
+ *
+ * Each value also carries the characters right before and after it in
+ * the text, which allows rewriting a param without scanning the text again.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Vec<DetailedMatch>}
+ */
+pub fn match_detailed (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Vec<DetailedMatch> {
+    token_captures(text, regexp, containers).unwrap_or_default().into_iter()
+        .map(|capture| DetailedMatch {
+            name: String::from(capture.token.name.as_str()),
+            value: capture.value,
+            group: capture.group,
+            subs: capture.subs,
+            leading_delim: text[..capture.start].chars().next_back(),
+            trailing_delim: text[capture.end..].chars().next()
+        })
+        .collect()
+}
+
+/**
+ * Match text like `match_str`, but return a trailing catch-all param (a
+ * repeat token such as `:rest*` that ends the pattern) separately from the
+ * route params, e.g. to hand the remaining path over to a sub-app.
+ *
+ * The catch-all is written as a named repeat param, `/app/:page/:rest*`
+ * or `:rest+` when it must not be empty. A bare `*rest` is not a param in
+ * this syntax but the literal `*rest`.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {(Vec<Match>, Option<Match>)}
+ */
+pub fn match_rest (text: &str, regexp: &FancyRegex, containers: &[Container]) -> (Vec<Match>, Option<Match>) {
+    let catch_all = match containers.last() {
+        Some(Container::Param(token)) if token.repeat => Some(token),
+        _ => None
+    };
     let mut matches: Vec<Match> = vec![];
+    let mut rest: Option<Match> = None;
+
+    for capture in token_captures(text, regexp, containers).unwrap_or_default() {
+        let token = capture.token;
+        let m = capture.into_match();
 
-    if !regexp.is_match(text).unwrap() {
-        return matches;
+        if catch_all.is_some_and(|catch_all| std::ptr::eq(catch_all, token)) {
+            rest = Some(m);
+        } else {
+            matches.push(m);
+        }
     }
-    
-    let containers: Vec<Container> = containers.into_iter()
-        .filter(|container| container.path == "")
-        .collect();
 
-    if let Some(caps) = regexp.captures_from_pos(&text, 0).unwrap() {
-        for i in 0..caps.len() {
-            let cap = caps.at(i).unwrap();
+    (matches, rest)
+}
 
-            if cap.len() == text.len() {
-                continue;
-            }
+/**
+ * Count the segments started in `text`, continuing a segment that is
+ * still `open` from the text before it.
+ *
+ * @param  {&str} text
+ * @param  {&str} delimiter
+ * @param  {&mut usize} count
+ * @param  {&mut bool} open
+ */
+fn count_segments (text: &str, delimiter: &str, count: &mut usize, open: &mut bool) {
+    let parts: Vec<&str> = if delimiter.is_empty() {
+        vec![text]
+    } else {
+        text.split(delimiter).collect()
+    };
 
-            let container = containers.get(i-1).unwrap();
-            if let Some(token) = &container.token {
-                matches.push(Match {
-                    name: String::from(token.name.as_str()),
-                    value: cap.to_owned()
-                });
-            }
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            *open = false;
+        }
+        if !part.is_empty() && !*open {
+            *count += 1;
+            *open = true;
         }
     }
+}
 
-    matches
-}
\ No newline at end of file
+/**
+ * Compute the minimum and maximum number of delimiter-separated segments
+ * a path matched by the containers can have. The maximum is `None` when
+ * a token can span any number of segments (repeat or custom pattern).
+ *
+ * Segments are the non-empty runs between the delimiters of the options
+ * the containers were parsed with, so the bounds can be compared against
+ * the `segment_count` of an input path to reject it before running the
+ * regexp.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&Options} options
+ * @return {(usize, Option<usize>)}
+ */
+pub fn segment_bounds (containers: &[Container], options: &Options) -> (usize, Option<usize>) {
+    let delimiter = options.delimiter.as_str();
+    let mut min = 0;
+    let mut max = Some(0);
+    let mut min_open = false;
+    let mut max_open = false;
+
+    for container in containers {
+        let token = match container {
+            Container::Literal(path) => {
+                count_segments(path.as_str(), delimiter, &mut min, &mut min_open);
+                if let Some(ref mut max) = max {
+                    count_segments(path.as_str(), delimiter, max, &mut max_open);
+                }
+                continue;
+            },
+            Container::Param(token) => token
+        };
+        // Only the generated pattern is known to match a non-empty value
+        // that never contains the delimiter.
+        let is_segment = token.pattern == default_pattern(&token.delimiter, delimiter);
+
+        if !token.optional {
+            count_segments(token.prefix.as_str(), delimiter, &mut min, &mut min_open);
+            if is_segment && !min_open {
+                min += 1;
+                min_open = true;
+            }
+            if is_segment && token.repeat {
+                min += token.min_repeat - 1;
+            }
+        }
+
+        if token.repeat || !is_segment {
+            max = None;
+        } else if let Some(ref mut max) = max {
+            count_segments(token.prefix.as_str(), delimiter, max, &mut max_open);
+            if !max_open {
+                *max += 1;
+                max_open = true;
+            }
+        }
+    }
+
+    (min, max)
+}
+
+/**
+ * Split containers into the groups matching each segment of a path, e.g.
+ * `/users/:id/posts` into `/users`, `/:id` and `/posts`, to build trie
+ * routers or match a path one segment at a time.
+ *
+ * Literals are split before every default delimiter, which stays at the
+ * start of its group, so joining the groups gives back the containers.
+ * Tokens are kept intact and start a new group when they have a prefix,
+ * even a repeat token spanning several segments of the path.
+ *
+ * @param  {&[Container]} containers
+ * @return {Vec<Vec<Container>>}
+ */
+pub fn split_segments (containers: &[Container]) -> Vec<Vec<Container>> {
+    let mut segments: Vec<Vec<Container>> = vec![];
+    let mut segment: Vec<Container> = vec![];
+    let mut path = String::new();
+
+    fn flush (path: &mut String, segment: &mut Vec<Container>) {
+        if !path.is_empty() {
+            segment.push(Container::Literal(std::mem::take(path)));
+        }
+    }
+
+    for container in containers {
+        match container {
+            Container::Param(token) => {
+                flush(&mut path, &mut segment);
+                if !token.prefix.is_empty() && !segment.is_empty() {
+                    segments.push(std::mem::take(&mut segment));
+                }
+                segment.push(container.clone());
+            },
+            Container::Literal(literal) => {
+                for c in literal.chars() {
+                    if c == DEFAULT_DELIMITER && !(path.is_empty() && segment.is_empty()) {
+                        flush(&mut path, &mut segment);
+                        segments.push(std::mem::take(&mut segment));
+                    }
+                    path.push(c);
+                }
+            }
+        }
+    }
+
+    flush(&mut path, &mut segment);
+    if !segment.is_empty() {
+        segments.push(segment);
+    }
+
+    segments
+}
+
+/**
+ * Join two parsed patterns without parsing their concatenation again, e.g.
+ * `/api` and `/users/:id` into `/api/users/:id`, giving the containers
+ * `parse` gives for the joined pattern with the same options. Fails when
+ * both patterns declare the same param.
+ *
+ * The literals at the join are merged, a param starting the suffix takes
+ * its prefix from the end of the base, so `/api/` and `:id?` give `/api`
+ * and `/:id?`, and un-named groups of the suffix are numbered after those
+ * of the base, so `/(a)` and `/(b)` give groups `0` and `1`.
+ *
+ * @param  {&[Container]} base
+ * @param  {&[Container]} suffix
+ * @param  {&Options} options
+ * @return {Result<Vec<Container>, PathError>}
+ */
+pub fn concat (base: &[Container], suffix: &[Container], options: &Options) -> Result<Vec<Container>, PathError> {
+    let offset = base.iter()
+        .filter_map(|container| container.token())
+        .filter(|token| token.unnamed)
+        .count();
+    let whitelist = prefix_whitelist(options);
+    let mut containers: Vec<Container> = base.to_vec();
+
+    for (i, container) in suffix.iter().enumerate() {
+        let mut token = match container {
+            Container::Literal(literal) => {
+                match containers.last_mut() {
+                    Some(Container::Literal(path)) => path.push_str(literal.as_str()),
+                    _ => containers.push(container.clone())
+                }
+                continue;
+            },
+            Container::Param(token) => token.clone()
+        };
+        if token.unnamed {
+            token.name = (token.name.parse::<usize>().unwrap_or(0) + offset).to_string();
+        }
+        if !token.name.is_empty() && containers.iter().any(|c| c.token().is_some_and(|t| t.name == token.name)) {
+            return Err(PathError::DuplicateParameter(token.name));
+        }
+
+        // The end of the base is the prefix, as if parsed together.
+        if let (0, true, Some(Container::Literal(path))) = (i, token.prefix.is_empty(), containers.last_mut()) {
+            let candidate = whitelist.iter()
+                .filter(|x| !x.is_empty() && path.ends_with(x.as_str()))
+                .max_by_key(|x| x.len());
+
+            if let Some(c) = candidate {
+                path.truncate(path.len() - c.len());
+                if token.pattern == fallback_pattern(&token.delimiter, options) {
+                    token.pattern = fallback_pattern(c, options);
+                }
+                token.prefix = c.clone();
+                token.delimiter = c.clone();
+            }
+            if path.is_empty() {
+                containers.pop();
+            }
+        }
+        containers.push(Container::Param(token));
+    }
+
+    Ok(containers)
+}
+
+/**
+ * Score the backtracking-prone constructs of a regexp string: quantifiers,
+ * quantified wildcards, lookarounds and quantifiers nested in quantified
+ * groups.
+ *
+ * @param  {&str} pattern
+ * @return {u32}
+ */
+fn pattern_cost (pattern: &str) -> u32 {
+    let mut cost = 0;
+    let mut chars = pattern.chars().peekable();
+    let mut class = false;
+    // Whether each open group holds a quantifier.
+    let mut groups: Vec<bool> = vec![false];
+    // What the previous character ended.
+    let mut quantified_group = false;
+    let mut wildcard = false;
+    let mut quantifier = false;
+
+    while let Some(c) = chars.next() {
+        let (was_quantified_group, was_wildcard, was_quantifier) = (quantified_group, wildcard, quantifier);
+        quantified_group = false;
+        wildcard = false;
+        quantifier = false;
+
+        match c {
+            '\\' => {
+                chars.next();
+            },
+            '[' if !class => {
+                class = true;
+                if chars.peek() == Some(&'^') {
+                    chars.next();
+                }
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                }
+            },
+            ']' if class => class = false,
+            _ if class => {},
+            '(' => {
+                if chars.peek() == Some(&'?') {
+                    chars.next();
+                    if chars.peek() == Some(&'<') {
+                        chars.next();
+                    }
+                    if let Some('=') | Some('!') = chars.peek() {
+                        cost += 3;
+                    }
+                }
+                groups.push(false);
+            },
+            ')' => {
+                quantified_group = groups.len() > 1 && groups.pop().unwrap();
+                if quantified_group {
+                    *groups.last_mut().unwrap() = true;
+                }
+            },
+            '.' => wildcard = true,
+            // A `?` right after a quantifier only makes it lazy.
+            '?' if was_quantifier => {},
+            '*' | '+' | '?' | '{' => {
+                if c == '{' {
+                    chars.by_ref().find(|&c| c == '}');
+                }
+                cost += 1;
+                if was_quantified_group {
+                    cost += 4;
+                }
+                if was_wildcard && c != '?' {
+                    cost += 2;
+                }
+                *groups.last_mut().unwrap() = true;
+                quantifier = true;
+            },
+            _ => {}
+        }
+    }
+
+    cost
+}
+
+/**
+ * Estimate how expensive matching the containers is, e.g. to try cheaper
+ * routes of a table first or flag expensive ones for review. Literals are
+ * free, every token costs 1 plus the backtracking-prone constructs of its
+ * pattern: quantifiers, `.*`-like wildcards, lookarounds and nested
+ * quantifiers such as repeats of a quantified pattern.
+ *
+ * This is a heuristic to compare patterns with, not a guarantee of the
+ * time a match takes.
+ *
+ * @param  {&[Container]} containers
+ * @return {u32}
+ */
+pub fn estimated_cost (containers: &[Container]) -> u32 {
+    containers.iter()
+        .filter_map(|container| container.token())
+        .map(|token| {
+            let optional = if token.optional { 1 } else { 0 };
+
+            1 + optional + pattern_cost(token_capture(token).as_str())
+        })
+        .sum()
+}
+
+/**
+ * Render a token back into its pattern syntax, e.g. `/:id(\\d+)?`.
+ *
+ * @param  {&Token} token
+ * @return {String}
+ */
+fn token_to_string (token: &Token) -> String {
+    // Only a single character is inferred as the prefix of a param.
+    let braced = token.prefix.chars().count() > 1;
+    let mut text = if braced {
+        format!("{{{}", token.prefix)
+    } else {
+        token.prefix.clone()
+    };
+    if token.name.is_empty() {
+        text.push_str(format!("(?:{})", unescape_group(token.pattern.as_str())).as_str());
+    } else if token.unnamed {
+        text.push_str(format!("({})", unescape_group(token.pattern.as_str())).as_str());
+    } else {
+        text.push(':');
+        text.push_str(token.name.as_str());
+
+        // The pattern is implied when it was built for the token delimiter.
+        let is_default = token.pattern == default_pattern(&token.delimiter, &token.delimiter)
+            || token.pattern == default_pattern(&token.delimiter, &DEFAULT_DELIMITER.to_string());
+        if !is_default {
+            text.push_str(format!("({})", unescape_group(token.pattern.as_str())).as_str());
+        }
+    }
+
+    if braced {
+        text.push('}');
+    }
+    text.push_str(token_modifier(token));
+
+    text
+}
+
+/**
+ * Get the modifier a token was written with.
+ *
+ * @param  {&Token} token
+ * @return {&str}
+ */
+fn token_modifier (token: &Token) -> &str {
+    if token.repeat && token.optional {
+        "*"
+    } else if token.repeat {
+        "+"
+    } else if token.optional {
+        "?"
+    } else {
+        ""
+    }
+}
+
+/**
+ * Percent-encode a value to be used as a path segment, keeping only the
+ * characters RFC 3986 allows in one: unreserved characters, sub-delims,
+ * `:` and `@`. Everything else, including `/`, `?`, `#`, `%` and spaces, is
+ * encoded byte by byte in UTF-8, so `a/b?c` becomes `a%2Fb%3Fc`.
+ *
+ * @param  {&str} value
+ * @return {String}
+ */
+pub fn encode_path_segment (value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+            | b'-' | b'.' | b'_' | b'~'
+            | b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'='
+            | b':' | b'@' => encoded.push(byte as char),
+            _ => encoded.push_str(format!("%{:02X}", byte).as_str())
+        }
+    }
+
+    encoded
+}
+
+/**
+ * Build a path from the containers, substituting every token by the value
+ * of its name in `params` after its prefix, e.g. `/users/42` for
+ * `/users/:id` and `id` = `42`. Optional tokens without a value are left
+ * out together with their prefix. A required non-capturing group, as in
+ * `/(?:foo|bar)/:id`, can not be filled and fails with `NonCapturingGroup`.
+ *
+ * Values are encoded with `encode_path_segment`. The value of a repeat
+ * token is split at the delimiter of the token first, so `a/b` for
+ * `/:path+` gives `/a/b` while `a/b` for `/:id` gives `/a%2Fb`.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&HashMap<String, String>} params
+ * @return {Result<String, CompileError>}
+ */
+pub fn compile (containers: &[Container], params: &HashMap<String, String>) -> Result<String, CompileError> {
+    compile_with(containers, params, false)
+}
+
+/**
+ * Build a path like `compile`. With `validate` set, every value is first
+ * checked against the pattern of its token, case sensitively like
+ * `param_regex`, so `a/b` for `/:id` fails instead of giving a path that
+ * matches back differently.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&HashMap<String, String>} params
+ * @param  {bool} validate
+ * @return {Result<String, CompileError>}
+ */
+pub fn compile_with (containers: &[Container], params: &HashMap<String, String>, validate: bool) -> Result<String, CompileError> {
+    compile_with_encoder(containers, params, validate, encode_path_segment)
+}
+
+/**
+ * Build a path like `compile_with`, encoding values with `encode` instead
+ * of `encode_path_segment`, e.g. to keep values which are already encoded
+ * as they are. Values are validated before they are encoded.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&HashMap<String, String>} params
+ * @param  {bool} validate
+ * @param  {Fn(&str) -> String} encode
+ * @return {Result<String, CompileError>}
+ */
+pub fn compile_with_encoder<F> (containers: &[Container], params: &HashMap<String, String>, validate: bool, encode: F) -> Result<String, CompileError>
+    where F: Fn(&str) -> String {
+    let mut path = String::new();
+
+    for container in containers {
+        let token = match container {
+            Container::Literal(literal) => {
+                path.push_str(literal.as_str());
+                continue;
+            },
+            Container::Param(token) => token
+        };
+        // Non-capturing tokens have no name to take a value from.
+        let value = match params.get(&token.name) {
+            Some(value) if !token.name.is_empty() => value,
+            _ if token.optional => continue,
+            _ if token.name.is_empty() => return Err(CompileError::NonCapturingGroup(token.pattern.clone())),
+            _ => return Err(CompileError::MissingParameter(token.name.clone()))
+        };
+
+        if validate && !value_matches(token, value) {
+            return Err(CompileError::InvalidParameterValue {
+                name: token.name.clone(),
+                value: value.clone()
+            });
+        }
+
+        path.push_str(token.prefix.as_str());
+        if token.repeat {
+            let segments: Vec<String> = value.split(token.delimiter.as_str()).map(&encode).collect();
+            path.push_str(segments.join(token.delimiter.as_str()).as_str());
+        } else {
+            path.push_str(encode(value).as_str());
+        }
+    }
+
+    Ok(path)
+}
+
+/**
+ * Check a whole value against the pattern of its token. A pattern which
+ * does not compile matches no value.
+ *
+ * @param  {&Token} token
+ * @param  {&str} value
+ * @return {bool}
+ */
+fn value_matches (token: &Token, value: &str) -> bool {
+    FancyRegex::new(format!("^(?:{})$", token_capture(token)).as_str())
+        .ok()
+        .and_then(|regexp| regexp.is_match(value).ok())
+        .unwrap_or(false)
+}
+
+/**
+ * Decode the percent-encoded bytes of a path segment, e.g. `john%20doe`
+ * into `john doe`. A value with a malformed escape, or which does not
+ * decode to UTF-8, is returned as it is.
+ *
+ * @param  {&str} value
+ * @return {String}
+ */
+pub fn decode_path_segment (value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        let hex = value.get(i + 1..i + 3).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => decoded.push(byte),
+            None => return String::from(value)
+        }
+        i += 3;
+    }
+
+    String::from_utf8(decoded).unwrap_or_else(|_| String::from(value))
+}
+
+/**
+ * Substitute the given params into the containers, leaving every param
+ * that is missing from `params` as its original placeholder.
+ *
+ * Missing params are kept whether they are required or optional, together
+ * with their prefix, custom pattern and modifier, so `/users/:id?` with no
+ * `id` stays `/users/:id?` and the result can be parsed and filled again
+ * later. Literal paths and provided values are escaped for that, e.g.
+ * `/a\\:b/:id` stays `/a\\:b/:id` rather than giving the param `b`. Encode
+ * values with `encode_path_segment` first to keep the path well formed.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&HashMap<String, String>} params
+ * @return {String}
+ */
+pub fn compile_partial (containers: &[Container], params: &HashMap<String, String>) -> String {
+    let mut path = String::new();
+
+    for container in containers {
+        let token = match container {
+            Container::Literal(text) => {
+                path.push_str(escape_literal(text).as_str());
+                continue;
+            },
+            Container::Param(token) => token
+        };
+        match params.get(&token.name) {
+            Some(value) => {
+                path.push_str(escape_literal(&token.prefix).as_str());
+                path.push_str(escape_literal(value).as_str());
+            },
+            None => path.push_str(token_to_string(token).as_str())
+        }
+    }
+
+    path
+}
+
+/**
+ * Escape the characters of a literal path which start a param, a group or
+ * a modifier in patterns, e.g. `/a\\:b` for the literal `/a:b`.
+ *
+ * @param  {&str} path
+ * @return {String}
+ */
+fn escape_literal (path: &str) -> String {
+    static LITERAL: OnceLock<Regex> = OnceLock::new();
+
+    let literal = LITERAL.get_or_init(|| Regex::new(r"([:()*+?{}\\])").unwrap());
+
+    literal.replace_all(path, r"\$1").into_owned()
+}
+
+/**
+ * Get the longest literal string every path matched by the containers
+ * starts with, e.g. `/users/` for `/users/:id`. Returns `None` when the
+ * pattern starts with a token that has no prefix.
+ *
+ * The regexp is case insensitive unless `sensitive` is set, so compare the
+ * prefix accordingly.
+ *
+ * @param  {&[Container]} containers
+ * @return {Option<String>}
+ */
+pub fn literal_prefix (containers: &[Container]) -> Option<String> {
+    let mut prefix = String::new();
+
+    for container in containers {
+        let token = match container {
+            Container::Literal(path) => {
+                prefix.push_str(path.as_str());
+                continue;
+            },
+            Container::Param(token) => token
+        };
+
+        // The prefix of a required token is a literal as well.
+        if !token.optional {
+            prefix.push_str(token.prefix.as_str());
+        }
+        break;
+    }
+
+    if prefix.is_empty() {
+        None
+    } else {
+        Some(prefix)
+    }
+}
+
+/**
+ * Get a standalone regexp validating a single value of the named param
+ * against its pattern, e.g. `^(?:\\d+)$` for `:id(\\d+)`. The regexp is
+ * case sensitive. Returns `None` when the containers have no such param,
+ * and an error when its pattern is not a valid regexp, as `parse` does not
+ * compile custom patterns.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&str} name
+ * @return {Result<Option<FancyRegex>, PathError>}
+ */
+pub fn param_regex (containers: &[Container], name: &str) -> Result<Option<FancyRegex>, PathError> {
+    containers.iter()
+        .filter_map(|container| container.token())
+        .find(|token| token.name == name)
+        .map(token_regex)
+        .transpose()
+}
+
+/**
+ * Compile the anchored regexp of a single token's value.
+ *
+ * @param  {&Token} token
+ * @return {Result<FancyRegex, PathError>}
+ */
+fn token_regex (token: &Token) -> Result<FancyRegex, PathError> {
+    compile_regexp(format!("^(?:{})$", token_capture(token)))
+}
+
+/**
+ * Count the delimiter-separated segments of an input path, to compare with
+ * `segment_bounds` under the same options. The raw text is counted just
+ * like the regexp matches it, so an encoded delimiter (`%2F`) never starts
+ * a new segment.
+ *
+ * @param  {&str} text
+ * @param  {&Options} options
+ * @return {usize}
+ */
+pub fn segment_count (text: &str, options: &Options) -> usize {
+    let mut count = 0;
+    let mut open = false;
+
+    count_segments(text, options.delimiter.as_str(), &mut count, &mut open);
+
+    count
+}
+
+/**
+ * Render the containers as a pattern for the JavaScript path-to-regexp
+ * library (v6 syntax), so routes shared with a JS frontend derive from the
+ * same definition.
+ *
+ * Characters with a meaning in JS patterns are escaped in literal paths.
+ * JS treats a preceding `/` or `.` as the prefix of a param, so optional
+ * and repeated params whose prefix would be inferred differently are
+ * wrapped in `{}`.
+ *
+ * @param  {&[Container]} containers
+ * @return {String}
+ */
+pub fn to_js_pattern (containers: &[Container]) -> String {
+    let mut pattern = String::new();
+
+    for container in containers {
+        let token = match container {
+            Container::Literal(path) => {
+                pattern.push_str(escape_literal(path).as_str());
+                continue;
+            },
+            Container::Param(token) => token
+        };
+        let text = token_to_string(token);
+        let modifier = token_modifier(token);
+        // The prefix only matters for optional and repeated params.
+        let same_prefix = if token.prefix.is_empty() {
+            !pattern.ends_with('/') && !pattern.ends_with('.')
+        } else {
+            token.prefix == "/" || token.prefix == "."
+        };
+
+        if modifier.is_empty() || same_prefix || text.starts_with('{') {
+            pattern.push_str(text.as_str());
+        } else {
+            pattern.push_str(format!("{{{}}}{}", &text[..text.len() - modifier.len()], modifier).as_str());
+        }
+    }
+
+    pattern
+}
+
+/**
+ * Tell whether the leading delimiter of `text` is missing, as in the
+ * pattern of the containers, or repeated. Meant for texts matched with
+ * `lenient_start`, to redirect to the canonical form. The delimiter is the
+ * one the containers were parsed with.
+ *
+ * @param  {&str} text
+ * @param  {&[Container]} containers
+ * @param  {&str} delimiter
+ * @return {LeadingDelimiter}
+ */
+pub fn leading_delimiter (text: &str, containers: &[Container], delimiter: &str) -> LeadingDelimiter {
+    let expected = match containers.first() {
+        Some(Container::Param(token)) => !token.optional && token.prefix.starts_with(delimiter),
+        Some(Container::Literal(path)) => path.starts_with(delimiter),
+        None => false
+    };
+    let found = (text.len() - text.trim_start_matches(delimiter).len()) / delimiter.len().max(1);
+
+    match (expected, found) {
+        (true, 0) => LeadingDelimiter::Missing,
+        (true, 1) | (false, 0) => LeadingDelimiter::Canonical,
+        _ => LeadingDelimiter::Extra
+    }
+}
+
+/**
+ * Generate the paths of every combination of the sample values of the
+ * params, e.g. for documentation or to test the regexp against its own
+ * output. Every path is built by `compile_with` with `validate` set, so
+ * values are encoded and optional params without samples are left out.
+ * Combinations which do not compile, because a sample does not match the
+ * pattern of its param or a required param has no samples, are skipped.
+ *
+ * Paths are generated lazily as the number of combinations grows quickly,
+ * so use `take` to cap them.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&HashMap<String, Vec<String>>} param_samples
+ * @return {impl Iterator<Item = String>}
+ */
+pub fn sample_paths<'a> (containers: &'a [Container], param_samples: &'a HashMap<String, Vec<String>>) -> impl Iterator<Item = String> + 'a {
+    let mut samples: Vec<(&String, &Vec<String>)> = vec![];
+    for token in containers.iter().filter_map(|container| container.token()) {
+        if let Some(values) = param_samples.get(&token.name) {
+            if samples.iter().all(|(name, _)| *name != &token.name) {
+                samples.push((&token.name, values));
+            }
+        }
+    }
+    let total = samples.iter()
+        .fold(1usize, |total, (_, values)| total.saturating_mul(values.len()));
+
+    (0..total).filter_map(move |mut i| {
+        let mut params: HashMap<String, String> = HashMap::new();
+
+        for (name, values) in samples.iter().rev() {
+            params.insert(name.to_string(), values[i % values.len()].clone());
+            i /= values.len();
+        }
+
+        compile_with(containers, &params, true).ok()
+    })
+}
+
+/**
+ * Combine the matches of chained prefix matches, e.g. of `/api/:version`
+ * and then `/users/:id` on the rest of the path, outer matches first.
+ *
+ * A param matched by both with the same value is kept once. Matching it
+ * with different values is an error rather than letting either one win.
+ *
+ * @param  {Vec<Match>} outer
+ * @param  {Vec<Match>} inner
+ * @return {Result<Vec<Match>, Conflict>}
+ */
+pub fn merge_matches (outer: Vec<Match>, inner: Vec<Match>) -> Result<Vec<Match>, Conflict> {
+    let mut matches = outer;
+
+    for m in inner {
+        match matches.iter().find(|o| o.name == m.name) {
+            Some(o) if o.value != m.value => {
+                return Err(Conflict {
+                    name: m.name,
+                    outer: o.value.clone(),
+                    inner: m.value
+                });
+            },
+            Some(_) => {},
+            None => matches.push(m)
+        }
+    }
+
+    Ok(matches)
+}
+
+/**
+ * Get the number of capture groups, besides the whole match, of the
+ * regexp `to_regexp` generates for the containers.
+ *
+ * @param  {&[Container]} containers
+ * @return {usize}
+ */
+pub fn capture_count (containers: &[Container]) -> usize {
+    containers.iter()
+        .filter_map(|container| container.token())
+        .map(|token| {
+            let own = if token.name.is_empty() { 0 } else { 1 };
+
+            own + count_groups(token_capture(token).as_str())
+        })
+        .sum()
+}
+
+/**
+ * Get the raw captures of a text matched by a regexp, for extraction the
+ * matches do not cover such as positions or groups nested in a custom
+ * pattern. For a regexp generated by `to_regexp`, group 0 is the whole
+ * match and groups 1 to `capture_count` are the generated groups in
+ * pattern order, each token followed by the groups of its pattern.
+ *
+ * @param  {&FancyRegex} regexp
+ * @param  {&str} text
+ * @return {Option<fancy_regex::Captures>}
+ */
+pub fn raw_captures<'t> (regexp: &FancyRegex, text: &'t str) -> Option<fancy_regex::Captures<'t>> {
+    captures_from(regexp, text, 0)
+}
+
+/**
+ * Translate containers parsed with the `from` delimiter to the `to`
+ * delimiter, e.g. to render a route matched on `/` with `.` separators
+ * through `compile_partial`.
+ *
+ * Every `from` in the literal paths and token prefixes is replaced, so the
+ * literals should not use it for anything but separating segments. Custom
+ * patterns are kept as they are, only the default pattern of a token is
+ * rebuilt for the new delimiter.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&str} from
+ * @param  {&str} to
+ * @return {Vec<Container>}
+ */
+pub fn translate_delimiter (containers: &[Container], from: &str, to: &str) -> Vec<Container> {
+    let translate = |text: &str| if from.is_empty() {
+        String::from(text)
+    } else {
+        text.replace(from, to)
+    };
+
+    containers.iter()
+        .map(|container| match container {
+            Container::Literal(path) => Container::Literal(translate(path)),
+            Container::Param(token) => {
+                let delimiter = translate(&token.delimiter);
+                let pattern = if token.pattern == default_pattern(&token.delimiter, from) {
+                    default_pattern(&delimiter, to)
+                } else {
+                    token.pattern.clone()
+                };
+
+                Container::Param(Token {
+                    name: token.name.clone(),
+                    unnamed: token.unnamed,
+                    prefix: translate(&token.prefix),
+                    delimiter,
+                    optional: token.optional,
+                    repeat: token.repeat,
+                    min_repeat: token.min_repeat,
+                    pattern,
+                    decode: token.decode
+                })
+            }
+        })
+        .collect()
+}
+
+/**
+ * Require the repeat token `name` to match at least `min` values when it
+ * is present, e.g. 2 for `/:path*` to match nothing or `/a/b` but not `/a`.
+ * Regexps built from the containers afterwards enforce it. Returns `false`
+ * when the containers have no repeat token with that name.
+ *
+ * @param  {&mut [Container]} containers
+ * @param  {&str} name
+ * @param  {usize} min
+ * @return {bool}
+ */
+pub fn set_min_repeat (containers: &mut [Container], name: &str, min: usize) -> bool {
+    let token = containers.iter_mut()
+        .filter_map(|container| match container {
+            Container::Literal(_) => None,
+            Container::Param(token) => Some(token)
+        })
+        .find(|token| token.repeat && token.name == name);
+
+    match token {
+        Some(token) => {
+            token.min_repeat = min.max(1);
+            true
+        },
+        None => false
+    }
+}
+
+/**
+ * Tell whether the literal parts of a text matched case insensitively
+ * differ in case from the pattern, e.g. `/Users/42` for `/users/:id`, so a
+ * router can redirect to the canonical casing. Returns `None` when the
+ * text does not match.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Option<bool>}
+ */
+pub fn is_case_folded (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Option<bool> {
+    let (start, end) = captures_from(regexp, text, 0)?.pos(0)?;
+    let captures = token_captures(text, regexp, containers)?;
+    let mut captures = captures.iter().peekable();
+    let mut expected = String::new();
+    let mut cursor = start;
+    // The text of non-capturing tokens is unknown, so the literals around
+    // them can't be compared.
+    let mut known = true;
+
+    fn differs_in_case (found: &str, expected: &str) -> bool {
+        found != expected && found.to_lowercase() == expected.to_lowercase()
+    }
+
+    for container in containers {
+        let token = match container {
+            Container::Literal(path) => {
+                expected.push_str(path.as_str());
+                continue;
+            },
+            Container::Param(token) => token
+        };
+
+        match captures.peek() {
+            Some(capture) if std::ptr::eq(capture.token, token) => {
+                expected.push_str(token.prefix.as_str());
+                if known && differs_in_case(&text[cursor..capture.start], expected.as_str()) {
+                    return Some(true);
+                }
+
+                cursor = capture.end;
+                expected.clear();
+                known = true;
+                captures.next();
+            },
+            _ if token.name.is_empty() => known = false,
+            _ => {}
+        }
+    }
+
+    // The match may end with a trailing delimiter after the literals.
+    let rest: String = text[cursor..end].chars().take(expected.chars().count()).collect();
+
+    Some(known && differs_in_case(rest.as_str(), expected.as_str()))
+}
+
+/**
+ * Match text and return the value of every param in pattern order, with
+ * `None` for optional params that did not match, e.g. to bind them to
+ * positional placeholders. Returns `None` when the text does not match.
+ *
+ * @param  {&str} text
+ * @param  {&FancyRegex} regexp
+ * @param  {&[Container]} containers
+ * @return {Option<Vec<Option<String>>>}
+ */
+pub fn match_positional (text: &str, regexp: &FancyRegex, containers: &[Container]) -> Option<Vec<Option<String>>> {
+    let mut captures = token_captures(text, regexp, containers)?.into_iter().peekable();
+
+    Some(containers.iter()
+        .filter_map(|container| container.token())
+        .filter(|token| !token.name.is_empty())
+        .map(|token| match captures.peek() {
+            Some(capture) if std::ptr::eq(capture.token, token) => captures.next().map(|capture| capture.into_match().value),
+            _ => None
+        })
+        .collect())
+}
+
+/**
+ * Match text against the containers, or when it does not match, get the
+ * length of the longest prefix of the text matching a prefix of the
+ * pattern. For `/users/:id(\\d+)` and `/users/abc` that is 7, the length of
+ * `/users/`, to report where the text stopped matching.
+ *
+ * The prefixes are compiled on failure only, so this is meant for
+ * diagnostics rather than for routing. Containers which do not give a
+ * valid regexp match nothing, so the length is 0.
+ *
+ * @param  {&str} text
+ * @param  {&[Container]} containers
+ * @param  {Options} options
+ * @return {Result<Vec<Match>, usize>}
+ */
+pub fn match_or_prefix_len (text: &str, containers: &[Container], options: Options) -> Result<Vec<Match>, usize> {
+    let sensitive = options.sensitive;
+    let regexp = match to_regexp(containers, options.clone()) {
+        Ok(regexp) => regexp,
+        Err(_) => return Err(0)
+    };
+
+    if let Some(captures) = token_captures(text, &regexp, containers) {
+        return Ok(captures.into_iter().map(TokenCapture::into_match).collect());
+    }
+
+    let mut prefix_options = options;
+    prefix_options.end = false;
+    prefix_options.strict = true;
+    let mut len = 0;
+    let mut next = 0;
+
+    for k in (1..=containers.len()).rev() {
+        let regexp = to_regexp(&containers[..k], prefix_options.clone());
+
+        if let Some((_, end)) = regexp.ok().and_then(|regexp| regexp.find(text).ok().flatten()) {
+            len = end;
+            next = k;
+            break;
+        }
+    }
+
+    // Continue into the literal, or the prefix of the token, the text
+    // stopped matching in.
+    if let Some(container) = containers.get(next) {
+        let literal = match container {
+            Container::Literal(path) => path.as_str(),
+            Container::Param(token) => token.prefix.as_str()
+        };
+        len += text[len..].chars()
+            .zip(literal.chars())
+            .take_while(|&(a, b)| a == b || (!sensitive && a.to_lowercase().eq(b.to_lowercase())))
+            .map(|(a, _)| a.len_utf8())
+            .sum::<usize>();
+    }
+
+    Err(len)
+}
+
+/**
+ * Extract matches from the captures of any regexp, naming the groups with
+ * an explicit map of group indexes to names, e.g. `[(1, "id")]` for a hand
+ * written `^/users/(\\d+)$`. Groups which did not participate are left out.
+ * Returns `None` when the text does not match.
+ *
+ * @param  {&FancyRegex} regexp
+ * @param  {&str} text
+ * @param  {&[(usize, String)]} names
+ * @return {Option<Vec<Match>>}
+ */
+pub fn extract_named (regexp: &FancyRegex, text: &str, names: &[(usize, String)]) -> Option<Vec<Match>> {
+    let caps = captures_from(regexp, text, 0)?;
+
+    Some(names.iter()
+        .filter_map(|(group, name)| {
+            let (start, end) = caps.pos(*group)?;
+            let value = String::from(&text[start..end]);
+
+            Some(Match {
+                name: name.clone(),
+                value: value.clone(),
+                raw_value: value.clone(),
+                values: vec![value],
+                start,
+                end
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn containers (pattern: &str) -> Vec<Container> {
+        parse(pattern, Options::default()).unwrap()
+    }
+
+    #[test]
+    fn segment_bounds_of_fixed_patterns () {
+        assert_eq!(segment_bounds(&containers("/users/:id"), &Options::default()), (2, Some(2)));
+        assert_eq!(segment_bounds(&containers("/users/:id/posts"), &Options::default()), (3, Some(3)));
+        assert_eq!(segment_bounds(&containers("/"), &Options::default()), (0, Some(0)));
+    }
+
+    #[test]
+    fn segment_bounds_of_optional_patterns () {
+        assert_eq!(segment_bounds(&containers("/users/:id?"), &Options::default()), (1, Some(2)));
+        assert_eq!(segment_bounds(&containers("/:a?/:b?"), &Options::default()), (0, Some(2)));
+    }
+
+    #[test]
+    fn segment_bounds_of_repeated_patterns () {
+        assert_eq!(segment_bounds(&containers("/files/:path+"), &Options::default()), (2, None));
+        assert_eq!(segment_bounds(&containers("/files/:path*"), &Options::default()), (1, None));
+        assert_eq!(segment_bounds(&containers("/files/(.*)"), &Options::default()), (1, None));
+    }
+
+    #[test]
+    fn segment_bounds_with_other_delimiters () {
+        let dot = Options::builder().delimiter(".").build();
+        let colons = Options::builder().delimiter("::").build();
+
+        assert_eq!(segment_bounds(&parse("a.:b.:c?", dot.clone()).unwrap(), &dot), (2, Some(3)));
+        assert_eq!(segment_bounds(&parse("a.:b+", dot.clone()).unwrap(), &dot), (2, None));
+        assert_eq!(segment_bounds(&parse("a/b.:c", dot.clone()).unwrap(), &dot), (2, Some(2)));
+        assert_eq!(segment_bounds(&parse("users:::id", colons.clone()).unwrap(), &colons), (2, Some(2)));
+    }
+
+    fn captured (pattern: &str, options: Options, text: &str) -> Option<Vec<(String, String)>> {
+        let containers = parse(pattern, options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+
+        token_captures(text, &regexp, &containers).map(|captures| captures.into_iter()
+            .map(|capture| capture.into_match())
+            .map(|m| (m.name, m.value))
+            .collect())
+    }
+
+    fn pairs (pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect()
+    }
+
+    fn params (pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect()
+    }
+
+    #[test]
+    fn compile_partial_keeps_missing_params () {
+        let containers = containers("/users/:id/posts/:post(\\d+)?");
+
+        assert_eq!(compile_partial(&containers, &params(&[])), "/users/:id/posts/:post(\\d+)?");
+        assert_eq!(compile_partial(&containers, &params(&[("id", "42")])), "/users/42/posts/:post(\\d+)?");
+        assert_eq!(compile_partial(&containers, &params(&[("post", "7")])), "/users/:id/posts/7");
+    }
+
+    #[test]
+    fn compile_partial_keeps_missing_groups () {
+        let containers = containers("/files/(.*)/:name");
+
+        assert_eq!(compile_partial(&containers, &params(&[("name", "a")])), "/files/(.*)/a");
+    }
+
+    #[test]
+    fn compile_partial_keeps_numeric_names () {
+        let containers = containers("/:5/(\\d+)");
+        let partial = compile_partial(&containers, &params(&[]));
+
+        assert_eq!(partial, "/:5/(\\d+)");
+        assert_eq!(parse_names(partial.as_str(), &Options::default()).unwrap(), ["5", "0"]);
+    }
+
+    #[test]
+    fn segment_strict_rejects_partial_segments () {
+        let strict = Options::builder().segment_strict(true).build();
+
+        assert_eq!(captured("/:a(\\d+):b", Options::default(), "/12ab"), Some(pairs(&[("a", "12"), ("b", "ab")])));
+        assert_eq!(captured("/:a(\\d+):b", strict.clone(), "/12ab"), None);
+        assert_eq!(captured("/:a(\\d+)-:b", Options::default(), "/1-2"), Some(pairs(&[("a", "1"), ("b", "2")])));
+        assert_eq!(captured("/:a(\\d+)-:b", strict, "/1-2"), None);
+    }
+
+    #[test]
+    fn segment_strict_matches_whole_segments () {
+        let strict = Options::builder().segment_strict(true).build();
+
+        assert_eq!(captured("/:a(\\d+)/:b", strict.clone(), "/12/ab"), Some(pairs(&[("a", "12"), ("b", "ab")])));
+        assert_eq!(captured("/:a(.*)/:b", strict, "/x/y/z"), Some(pairs(&[("a", "x/y"), ("b", "z")])));
+    }
+
+    #[test]
+    fn match_rest_separates_the_catch_all () {
+        let containers = containers("/app/:page/:rest*");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let (matches, rest) = match_rest("/app/home/a/b/c", &regexp, &containers);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].name(), matches[0].value()), ("page", "home"));
+        let rest = rest.unwrap();
+        assert_eq!((rest.name(), rest.value()), ("rest", "a/b/c"));
+    }
+
+    #[test]
+    fn match_rest_without_a_catch_all () {
+        let containers = containers("/app/:page/:id");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let (matches, rest) = match_rest("/app/home/1", &regexp, &containers);
+
+        assert_eq!(matches.len(), 2);
+        assert!(rest.is_none());
+    }
+
+    #[test]
+    fn bare_star_is_a_literal () {
+        assert!(containers("/app/:page/*rest").last().unwrap().token().is_none());
+    }
+
+    #[test]
+    fn literal_prefix_of_patterns () {
+        assert_eq!(literal_prefix(&containers("/users/:id")), Some(String::from("/users/")));
+        assert_eq!(literal_prefix(&containers("/users/:id/posts")), Some(String::from("/users/")));
+        assert_eq!(literal_prefix(&containers("/users/:id?")), Some(String::from("/users")));
+        assert_eq!(literal_prefix(&containers("/files/:name.:ext")), Some(String::from("/files/")));
+        assert_eq!(literal_prefix(&containers("/about")), Some(String::from("/about")));
+        assert_eq!(literal_prefix(&containers("/:id")), Some(String::from("/")));
+    }
+
+    #[test]
+    fn literal_prefix_of_leading_tokens () {
+        assert_eq!(literal_prefix(&containers(":id")), None);
+        assert_eq!(literal_prefix(&containers("/:id?")), None);
+        assert_eq!(literal_prefix(&containers("(.*)")), None);
+    }
+
+    #[test]
+    fn param_regex_of_named_params () {
+        let containers = containers("/users/:id/:post(\\d+)");
+        let id = param_regex(&containers, "id").unwrap().unwrap();
+        let post = param_regex(&containers, "post").unwrap().unwrap();
+
+        assert!(is_match("42", &id));
+        assert!(is_match("abc", &id));
+        assert!(!is_match("a/b", &id));
+        assert!(is_match("42", &post));
+        assert!(!is_match("4a", &post));
+        assert!(!is_match("", &post));
+    }
+
+    #[test]
+    fn param_regex_of_missing_params () {
+        assert!(param_regex(&containers("/users/:id"), "name").unwrap().is_none());
+    }
+
+    #[test]
+    fn param_regex_of_invalid_patterns () {
+        let containers = containers("/:id([)");
+
+        assert!(matches!(param_regex(&containers, "id"), Err(PathError::InvalidRegex { .. })));
+    }
+
+    #[test]
+    fn param_regex_is_cached_on_the_matcher () {
+        let matcher = PathMatcher::new("/users/:id(\\d+)", Options::default()).unwrap();
+        let id = matcher.param_regex("id").unwrap().unwrap();
+
+        assert!(is_match("42", id));
+        assert!(!is_match("abc", id));
+        assert!(std::ptr::eq(id, matcher.param_regex("id").unwrap().unwrap()));
+        assert!(matcher.param_regex("name").unwrap().is_none());
+    }
+
+    #[test]
+    fn segment_count_of_encoded_delimiters () {
+        assert_eq!(segment_count("/a/b", &Options::default()), 2);
+        assert_eq!(segment_count("/a%2Fb", &Options::default()), 1);
+        assert_eq!(segment_count("/a%2fb/c", &Options::default()), 2);
+        assert_eq!(segment_count("/", &Options::default()), 0);
+    }
+
+    #[test]
+    fn segment_count_with_other_delimiters () {
+        let dot = Options::builder().delimiter(".").build();
+        let colons = Options::builder().delimiter("::").build();
+
+        assert_eq!(segment_count("a.b..c", &dot), 3);
+        assert_eq!(segment_count("a/b.c", &dot), 2);
+        assert_eq!(segment_count("users::42", &colons), 2);
+        assert_eq!(segment_count("a:b::c", &colons), 2);
+    }
+
+    #[test]
+    fn segment_count_agrees_with_the_regexp () {
+        for (pattern, text) in [("/users/:id", "/users/a%2Fb"), ("/:a/:b", "/x%2Fy/z"), ("/files/:path+", "/files/a%2Fb/c")] {
+            let containers = containers(pattern);
+            let regexp = to_regexp(&containers, Options::default()).unwrap();
+            let (min, max) = segment_bounds(&containers, &Options::default());
+            let count = segment_count(text, &Options::default());
+
+            assert!(is_match(text, &regexp));
+            assert!(count >= min && max.map_or(true, |max| count <= max), "{} {}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn to_js_pattern_round_trips () {
+        for pattern in ["/users/:id", "/users/:id?", "/files/:path*", "/files/:path+", "/:id(\\d+)", "/(.*)", "/file.:ext?"] {
+            assert_eq!(to_js_pattern(&containers(pattern)), pattern);
+        }
+    }
+
+    #[test]
+    fn to_js_pattern_escapes_literals () {
+        assert_eq!(to_js_pattern(&containers("/a\\:b/:id")), "/a\\:b/:id");
+        assert_eq!(to_js_pattern(&containers("/a\\(b\\)+")), "/a\\(b\\)\\+");
+    }
+
+    #[test]
+    fn to_js_pattern_wraps_other_prefixes () {
+        let dash = Options::builder().whitelist(["/", "-"]).build();
+        let slash = Options::builder().whitelist(["/"]).build();
+
+        assert_eq!(to_js_pattern(&parse("/:a-:b?", dash.clone()).unwrap()), "/:a{-:b}?");
+        assert_eq!(to_js_pattern(&parse("/:a-:b", dash).unwrap()), "/:a-:b");
+        assert_eq!(to_js_pattern(&parse("/files/:name.:ext?", slash).unwrap()), "/files/:name.{:ext}?");
+    }
+
+    #[test]
+    fn match_detailed_returns_nested_groups () {
+        let containers = containers("/at/:coord((\\d+),(\\d+))/:zoom");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let matches = match_detailed("/at/12,34/5", &regexp, &containers);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].name(), matches[0].value(), matches[0].group()), ("coord", "12,34", 1));
+        assert_eq!(matches[0].subs(), [Some(String::from("12")), Some(String::from("34"))]);
+        assert_eq!((matches[1].name(), matches[1].value(), matches[1].group()), ("zoom", "5", 4));
+        assert!(matches[1].subs().is_empty());
+    }
+
+    #[test]
+    fn match_str_skips_nested_groups () {
+        assert_eq!(captured("/at/:coord((\\d+),(\\d+))/:zoom", Options::default(), "/at/12,34/5"), Some(pairs(&[("coord", "12,34"), ("zoom", "5")])));
+    }
+
+    #[test]
+    fn ends_with_stops_at_each_terminator () {
+        let options = Options::builder().ends_with(["?", "#"]).build();
+        let containers = parse("/foo", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options.clone()).unwrap();
+
+        assert_eq!(to_regexp_string(&containers, &options), r"(?i)^\/foo(?:\/)?(?:(?=\?)|(?=#)|(?=$))");
+        assert!(is_match("/foo", &regexp));
+        assert!(is_match("/foo?x", &regexp));
+        assert!(is_match("/foo#y", &regexp));
+        assert!(is_match("/foo/?x", &regexp));
+        assert!(!is_match("/foo&x", &regexp));
+        assert!(!is_match("/foobar", &regexp));
+    }
+
+    #[test]
+    fn ends_with_leaves_terminators_out_of_params () {
+        let options = Options::builder().ends_with(["?", "#"]).build();
+
+        assert_eq!(captured("/users/:id", options.clone(), "/users/5?x=1"), Some(pairs(&[("id", "5")])));
+        assert_eq!(captured("/users/:id", options, "/users/5#top"), Some(pairs(&[("id", "5")])));
+    }
+
+    #[test]
+    fn keep_unnamed_drops_positional_groups () {
+        let dropped = Options::builder().keep_unnamed(false).build();
+
+        assert_eq!(captured("/:a/(foo|bar)", Options::default(), "/x/foo"), Some(pairs(&[("a", "x"), ("0", "foo")])));
+        assert_eq!(captured("/:a/(foo|bar)", dropped.clone(), "/x/foo"), Some(pairs(&[("a", "x")])));
+        assert_eq!(captured("/:a/(foo|bar)/:b", dropped.clone(), "/x/bar/y"), Some(pairs(&[("a", "x"), ("b", "y")])));
+        assert_eq!(captured("/:a/(foo|bar)", dropped, "/x/baz"), None);
+    }
+
+    #[test]
+    fn non_capturing_groups_are_never_kept () {
+        assert_eq!(captured("/:a/(?:foo|bar)", Options::default(), "/x/bar"), Some(pairs(&[("a", "x")])));
+        assert_eq!(parse_names("/:a/(?:foo|bar)/(baz)", &Options::default()).unwrap(), ["a", "0"]);
+    }
+
+    #[test]
+    fn lenient_start_matches_either_form () {
+        let options = Options::builder().lenient_start(true).build();
+
+        assert_eq!(captured("/users/:id", options.clone(), "/users/42"), Some(pairs(&[("id", "42")])));
+        assert_eq!(captured("/users/:id", options.clone(), "users/42"), Some(pairs(&[("id", "42")])));
+        assert_eq!(captured("/users/:id", options, "//users/42"), Some(pairs(&[("id", "42")])));
+        assert_eq!(captured("/users/:id", Options::default(), "users/42"), None);
+    }
+
+    #[test]
+    fn leading_delimiter_reports_the_form () {
+        let containers = containers("/users/:id");
+
+        assert_eq!(leading_delimiter("/users/42", &containers, "/"), LeadingDelimiter::Canonical);
+        assert_eq!(leading_delimiter("users/42", &containers, "/"), LeadingDelimiter::Missing);
+        assert_eq!(leading_delimiter("//users/42", &containers, "/"), LeadingDelimiter::Extra);
+    }
+
+    #[test]
+    fn leading_delimiter_of_other_delimiters () {
+        let options = Options::builder().delimiter("::").build();
+        let containers = parse("::users::id", options).unwrap();
+
+        assert_eq!(leading_delimiter("::users::7", &containers, "::"), LeadingDelimiter::Canonical);
+        assert_eq!(leading_delimiter("users::7", &containers, "::"), LeadingDelimiter::Missing);
+        assert_eq!(leading_delimiter("::::users::7", &containers, "::"), LeadingDelimiter::Extra);
+    }
+
+    #[test]
+    fn router_uses_its_default_options () {
+        let mut router = Router::new(Options::builder().sensitive(true).build());
+        router.add("user", "/users/:id").unwrap();
+
+        let (name, matches) = router.find("/users/42").unwrap();
+        assert_eq!((name, matches[0].value()), ("user", "42"));
+        assert!(router.find("/USERS/42").is_none());
+    }
+
+    #[test]
+    fn router_overrides_compose_with_defaults () {
+        let mut router = Router::new(Options::builder().sensitive(true).build());
+        router.add_with("loose", "/loose/:id", Options::default()).unwrap();
+        router.add_with("strict", "/strict/:id", Options::builder().sensitive(true).strict(true).build()).unwrap();
+        router.add("user", "/users/:id").unwrap();
+
+        assert_eq!(router.find("/LOOSE/1").map(|(name, _)| name), Some("loose"));
+        assert_eq!(router.find("/strict/1").map(|(name, _)| name), Some("strict"));
+        assert!(router.find("/strict/1/").is_none());
+        assert!(router.find("/STRICT/1").is_none());
+        assert_eq!(router.find("/users/1/").map(|(name, _)| name), Some("user"));
+    }
+
+    #[test]
+    fn router_tries_the_fallback_last () {
+        let mut router = Router::new(Options::default());
+        router.set_fallback("missing", "(.*)").unwrap();
+        router.add("user", "/users/:id").unwrap();
+
+        assert_eq!(router.find("/users/1").map(|(name, _)| name), Some("user"));
+        assert_eq!(router.find("/other").map(|(name, _)| name), Some("missing"));
+    }
+
+    #[test]
+    fn required_params_reject_empty_values () {
+        assert_eq!(captured("/:x(.*)", Options::default(), "/"), None);
+        assert_eq!(captured("/:x(.*)", Options::default(), ""), None);
+        assert_eq!(captured("/:x(.*)", Options::default(), "/a/b"), Some(pairs(&[("x", "a/b")])));
+        assert_eq!(captured("/:x", Options::default(), "/"), None);
+        assert!(!is_match("/", &to_regexp(&containers("/:x(.*)"), Options::default()).unwrap()));
+    }
+
+    #[test]
+    fn optional_params_may_be_left_out () {
+        assert_eq!(captured("/:x(.*)?", Options::default(), "/"), Some(pairs(&[("x", "")])));
+        assert_eq!(captured("/:x(.*)?", Options::default(), ""), Some(vec![]));
+        assert_eq!(captured("/:x(.*)*", Options::default(), "/"), Some(pairs(&[("x", "")])));
+        assert_eq!(captured("/:x?", Options::default(), "/"), Some(vec![]));
+        assert!(is_match("/", &to_regexp(&containers("/:x(.*)?"), Options::default()).unwrap()));
+    }
+
+    #[test]
+    fn empty_captures_agree_with_is_match () {
+        for (pattern, text, expected) in [
+            ("/:a(.*)/:b(.*)", "/x/y/", pairs(&[("a", "x"), ("b", "y/")])),
+            ("/:a(.*)-:b(.*)", "/x-y-", pairs(&[("a", "x"), ("b", "y-")]))
+        ] {
+            let containers = containers(pattern);
+            let regexp = to_regexp(&containers, Options::default()).unwrap();
+            let mut router = Router::new(Options::default());
+            router.add("route", pattern).unwrap();
+
+            assert!(is_match(text, &regexp));
+            assert_eq!(match_str(text, &regexp, &containers).len(), 2);
+            assert_eq!(captured(pattern, Options::default(), text), Some(expected));
+            assert!(router.find(text).is_some());
+        }
+    }
+
+    #[test]
+    fn boundary_finds_paths_in_text () {
+        let options = Options::builder().boundary(true).build();
+        let containers = parse("/users/:id", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+        let (start, end, matches) = search("GET /users/42 HTTP/1.1", &regexp, &containers).unwrap();
+
+        assert_eq!((start, end), (4, 13));
+        assert_eq!((matches[0].name(), matches[0].value()), ("id", "42"));
+    }
+
+    #[test]
+    fn boundary_needs_whitespace_around_paths () {
+        let options = Options::builder().boundary(true).build();
+        let containers = parse("/users/:id", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+
+        assert!(is_match("/users/42", &regexp));
+        assert!(!is_match("GET /api/users/42 HTTP/1.1", &regexp));
+        assert!(!is_match("GET x/users/42", &regexp));
+        assert_eq!(match_iter("/users/1 /users/2", &regexp, &containers).count(), 2);
+    }
+
+    #[test]
+    fn int_range_matches_its_bounds () {
+        for (min, max) in [(0, 255), (1, 255), (7, 7), (10, 99), (95, 1050), (0, 9)] {
+            let range = Regex::new(format!("^(?:{})$", patterns::int_range(min, max)).as_str()).unwrap();
+
+            for n in min.saturating_sub(20)..=max + 20 {
+                assert_eq!(range.is_match(n.to_string().as_str()), (min..=max).contains(&n), "{} in {}-{}", n, min, max);
+            }
+            assert!(!range.is_match(format!("0{}", min).as_str()));
+        }
+    }
+
+    #[test]
+    fn int_range_in_a_pattern () {
+        let pattern = format!("/:n({})", patterns::int_range(1, 255));
+
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/255"), Some(pairs(&[("n", "255")])));
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/256"), None);
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/0"), None);
+    }
+
+    #[test]
+    fn translate_delimiter_compiles_with_other_separators () {
+        let dotted = translate_delimiter(&containers("/users/:id/posts/:post?"), "/", ".");
+
+        assert_eq!(compile(&dotted, &params(&[("id", "42"), ("post", "7")])).unwrap(), ".users.42.posts.7");
+        assert_eq!(compile(&dotted, &params(&[("id", "42")])).unwrap(), ".users.42.posts");
+        assert_eq!(compile_partial(&dotted, &params(&[])), ".users.:id.posts.:post?");
+    }
+
+    #[test]
+    fn translate_delimiter_round_trips () {
+        let dot = Options::builder().delimiter(".").build();
+        let slashed = containers("/users/:id");
+        let dotted = translate_delimiter(&slashed, "/", ".");
+        let path = compile(&dotted, &params(&[("id", "42")])).unwrap();
+        let regexp = to_regexp(&dotted, dot.clone()).unwrap();
+
+        assert_eq!(match_str(path.as_str(), &regexp, &dotted)[0].value(), "42");
+        assert!(!is_match(".users.4.2", &regexp));
+        assert_eq!(to_regexp_string(&translate_delimiter(&dotted, ".", "/"), &Options::default()), to_regexp_string(&slashed, &Options::default()));
+        assert_eq!(to_regexp_string(&dotted, &dot), to_regexp_string(&parse(".users.:id", dot.clone()).unwrap(), &dot));
+    }
+
+    fn case_folded (pattern: &str, text: &str) -> Option<bool> {
+        let containers = containers(pattern);
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+
+        is_case_folded(text, &regexp, &containers)
+    }
+
+    #[test]
+    fn is_case_folded_flags_literal_case () {
+        assert_eq!(case_folded("/users/:id", "/Users/42"), Some(true));
+        assert_eq!(case_folded("/users/:id", "/USERS/42"), Some(true));
+        assert_eq!(case_folded("/users/:id/posts", "/users/42/Posts"), Some(true));
+        assert_eq!(case_folded("/users/:id", "/users/42"), Some(false));
+        assert_eq!(case_folded("/users/:id", "/users/ABC"), Some(false));
+    }
+
+    #[test]
+    fn is_case_folded_of_other_texts () {
+        assert_eq!(case_folded("/users/:id", "/accounts/42"), None);
+        assert_eq!(case_folded("/users/:id", "/users/42/"), Some(false));
+    }
+
+    fn positional (pattern: &str, text: &str) -> Option<Vec<Option<String>>> {
+        let containers = containers(pattern);
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+
+        match_positional(text, &regexp, &containers)
+    }
+
+    #[test]
+    fn match_positional_keeps_holes () {
+        let some = |value: &str| Some(String::from(value));
+
+        assert_eq!(positional("/:a/:b?/:c", "/x/y/z"), Some(vec![some("x"), some("y"), some("z")]));
+        assert_eq!(positional("/:a/:b?/:c", "/x/z"), Some(vec![some("x"), None, some("z")]));
+        assert_eq!(positional("/:a?/:b?", "/y"), Some(vec![some("y"), None]));
+        assert_eq!(positional("/:a?/:b?", ""), Some(vec![None, None]));
+        assert_eq!(positional("/:a/(?:x|y)/(\\d+)?", "/a/x"), Some(vec![some("a"), None]));
+    }
+
+    #[test]
+    fn match_positional_of_other_texts () {
+        assert_eq!(positional("/:a/:b?/:c", "/x"), None);
+    }
+
+    fn prefix_len (pattern: &str, text: &str) -> Result<usize, usize> {
+        match_or_prefix_len(text, &containers(pattern), Options::default()).map(|matches| matches.len())
+    }
+
+    #[test]
+    fn match_or_prefix_len_of_near_misses () {
+        assert_eq!(prefix_len("/users/:id(\\d+)", "/users/abc"), Err(7));
+        assert_eq!(prefix_len("/users/:id/posts", "/users/42/comments"), Err(10));
+        assert_eq!(prefix_len("/a/b/c", "/a/b/x"), Err(5));
+        assert_eq!(prefix_len("/users/:id", "/accounts"), Err(1));
+        assert_eq!(prefix_len("/users", "accounts"), Err(0));
+    }
+
+    #[test]
+    fn match_or_prefix_len_of_matches () {
+        assert_eq!(prefix_len("/users/:id", "/users/42"), Ok(1));
+        assert_eq!(prefix_len("/about", "/about"), Ok(0));
+    }
+
+    #[test]
+    fn repeated_params_keep_the_raw_capture () {
+        let containers = containers("/:parts+");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let matches = match_str("/a/b/c", &regexp, &containers);
+
+        assert_eq!(matches[0].value(), "a/b/c");
+        assert_eq!(matches[0].raw_value(), "a/b/c");
+        assert_eq!(matches[0].values(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn repeated_params_split_before_decoding () {
+        let options = Options::builder().decode(true).build();
+        let containers = parse("/:parts*", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+        let matches = match_str("/a%2Fb/c%20d", &regexp, &containers);
+
+        assert_eq!(matches[0].value(), "a/b/c d");
+        assert_eq!(matches[0].raw_value(), "a%2Fb/c%20d");
+        assert_eq!(matches[0].values(), ["a/b", "c d"]);
+        assert!(match_str("/", &regexp, &containers).is_empty());
+    }
+
+    fn turkish (c: char) -> char {
+        match c {
+            'I' => 'ı',
+            'İ' => 'i',
+            c => c.to_lowercase().next().unwrap_or(c)
+        }
+    }
+
+    #[test]
+    fn fold_compares_literal_routes () {
+        let mut router = Router::new(Options::builder().fold(turkish).build());
+        router.add("red", "/kırmızı").unwrap();
+        router.add("list", "/liste").unwrap();
+
+        assert_eq!(router.find("/KIRMIZI").map(|(name, _)| name), Some("red"));
+        assert_eq!(router.find("/LİSTE").map(|(name, _)| name), Some("list"));
+        assert!(router.find("/LISTE").is_none());
+    }
+
+    #[test]
+    fn default_folding_of_literal_routes () {
+        let mut router = Router::new(Options::default());
+        router.add("red", "/kırmızı").unwrap();
+        router.add("list", "/liste").unwrap();
+
+        assert!(router.find("/KIRMIZI").is_none());
+        assert_eq!(router.find("/LISTE").map(|(name, _)| name), Some("list"));
+    }
+
+    fn segments (pattern: &str) -> Vec<String> {
+        split_segments(&containers(pattern)).iter().map(|segment| to_js_pattern(segment)).collect()
+    }
+
+    #[test]
+    fn split_segments_at_delimiters () {
+        assert_eq!(segments("/users/:id/posts"), ["/users", "/:id", "/posts"]);
+        assert_eq!(segments("/api/v1/users/:id"), ["/api", "/v1", "/users", "/:id"]);
+        assert_eq!(segments("/users/"), ["/users", "/"]);
+        assert_eq!(segments("/"), ["/"]);
+    }
+
+    #[test]
+    fn split_segments_keeps_tokens_intact () {
+        assert_eq!(segments("/a-:b/c"), ["/a-:b", "/c"]);
+        assert_eq!(segments("/files/:path*/raw"), ["/files", "/:path*", "/raw"]);
+        assert_eq!(segments("/:id.:ext"), ["/:id", ".:ext"]);
+    }
+
+    #[test]
+    fn set_min_repeat_requires_values_when_present () {
+        let mut containers = containers("/files/:path*");
+        assert!(set_min_repeat(&mut containers, "path", 2));
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+
+        assert!(is_match("/files", &regexp));
+        assert!(!is_match("/files/a", &regexp));
+        assert_eq!(match_str("/files/a/b", &regexp, &containers)[0].values(), ["a", "b"]);
+        assert_eq!(match_str("/files/a/b/c", &regexp, &containers)[0].values(), ["a", "b", "c"]);
+        assert_eq!(containers[1].token().unwrap().min_repeat(), 2);
+    }
+
+    #[test]
+    fn set_min_repeat_of_other_tokens () {
+        let mut containers = containers("/files/:name/:path+");
+
+        assert!(!set_min_repeat(&mut containers, "name", 2));
+        assert!(!set_min_repeat(&mut containers, "missing", 2));
+        assert!(set_min_repeat(&mut containers, "path", 3));
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        assert!(!is_match("/files/x/a/b", &regexp));
+        assert!(is_match("/files/x/a/b/c", &regexp));
+    }
+
+    #[test]
+    fn extract_named_with_a_name_map () {
+        let regexp = FancyRegex::new(r"^/users/(\d+)(?:/(\w+))?(?:/(x))?$").unwrap();
+        let names = [(1, String::from("id")), (2, String::from("tab")), (3, String::from("x"))];
+        let matches = extract_named(&regexp, "/users/42/posts", &names).unwrap();
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].name(), matches[0].value(), matches[0].start(), matches[0].end()), ("id", "42", 7, 9));
+        assert_eq!((matches[1].name(), matches[1].value()), ("tab", "posts"));
+        assert_eq!(extract_named(&regexp, "/users/42", &names).unwrap().len(), 1);
+        assert!(extract_named(&regexp, "/users/abc", &names).is_none());
+    }
+
+    #[test]
+    fn extract_named_of_unknown_groups () {
+        let regexp = FancyRegex::new(r"^/(\w+)$").unwrap();
+
+        assert!(extract_named(&regexp, "/a", &[(5, String::from("five"))]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn dollar_end_of_trailing_newlines () {
+        let multi_line = Options::builder().multi_line(true).build();
+        let containers = containers("/foo");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let lines = to_regexp(&containers, multi_line).unwrap();
+
+        assert!(is_match("/foo", &regexp));
+        assert!(!is_match("/foo\n", &regexp));
+        assert!(is_match("/foo\n", &lines));
+    }
+
+    #[test]
+    fn absolute_end_rejects_a_trailing_newline () {
+        let options = Options::builder().multi_line(true).absolute_end(true).build();
+        let containers = parse("/foo", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options.clone()).unwrap();
+
+        assert!(to_regexp_string(&containers, &options).ends_with(r"(?:\/)?\z"));
+        assert!(is_match("/foo", &regexp));
+        assert!(is_match("/foo/", &regexp));
+        assert!(!is_match("/foo\n", &regexp));
+    }
+
+    #[test]
+    fn encode_path_segment_of_reserved_characters () {
+        assert_eq!(encode_path_segment("a/b?c"), "a%2Fb%3Fc");
+        assert_eq!(encode_path_segment("a b#c%d"), "a%20b%23c%25d");
+        assert_eq!(encode_path_segment("ü"), "%C3%BC");
+        assert_eq!(encode_path_segment("a-b_c.d~e!$&'()*+,;=:@"), "a-b_c.d~e!$&'()*+,;=:@");
+    }
+
+    #[test]
+    fn compile_encodes_values () {
+        let containers = containers("/users/:name/:path*");
+
+        assert_eq!(compile(&containers, &params(&[("name", "a/b?c")])).unwrap(), "/users/a%2Fb%3Fc");
+        assert_eq!(compile(&containers, &params(&[("name", "a b"), ("path", "x y/z#")])).unwrap(), "/users/a%20b/x%20y/z%23");
+    }
+
+    #[test]
+    fn case_insensitive_matches_preserve_values () {
+        let containers = containers("/user/:name");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let matches = match_str("/USER/JohnDoe", &regexp, &containers);
+
+        assert_eq!(matches[0].value(), "JohnDoe");
+        assert_eq!(matches[0].raw_value(), "JohnDoe");
+        assert_eq!(match_map("/User/JohnDoe", &regexp, &containers).unwrap()["name"], "JohnDoe");
+        assert_eq!(match_param("/user/JOHN", &regexp, &containers, "name"), Some(String::from("JOHN")));
+    }
+
+    #[test]
+    fn sensitive_matches_compare_case () {
+        let options = Options::builder().sensitive(true).build();
+
+        assert_eq!(captured("/user/:name", options.clone(), "/user/JohnDoe"), Some(pairs(&[("name", "JohnDoe")])));
+        assert_eq!(captured("/user/:name", options, "/USER/JohnDoe"), None);
+    }
+
+    #[test]
+    fn fallback_never_shadows_routes () {
+        let mut router = Router::new(Options::default());
+        router.set_fallback("not_found", "/:path*").unwrap();
+        router.add("home", "/").unwrap();
+        router.add("user", "/users/:id").unwrap();
+
+        assert_eq!(router.find("/").map(|(name, _)| name), Some("home"));
+        assert_eq!(router.find("/users/1").map(|(name, _)| name), Some("user"));
+        let (name, matches) = router.find("/users/1/posts").unwrap();
+        assert_eq!((name, matches[0].value()), ("not_found", "users/1/posts"));
+    }
+
+    #[test]
+    fn fallback_can_miss_too () {
+        let mut router = Router::new(Options::default());
+        router.add("user", "/users/:id").unwrap();
+        assert!(router.find("/other").is_none());
+
+        router.set_fallback("api", "/api/(.*)").unwrap();
+        assert!(router.find("/other").is_none());
+        assert_eq!(router.find("/api/x").map(|(name, _)| name), Some("api"));
+    }
+
+    #[test]
+    fn one_of_matches_listed_values () {
+        let pattern = format!("/:method({})", patterns::one_of(&["GET", "POST", "PUT"]));
+
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/POST"), Some(pairs(&[("method", "POST")])));
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/PUT"), Some(pairs(&[("method", "PUT")])));
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/PATCH"), None);
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/GETX"), None);
+    }
+
+    #[test]
+    fn one_of_escapes_values () {
+        let pattern = format!("/:v({})", patterns::one_of(&["a.b", "c+", "$x", "a"]));
+
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/a.b"), Some(pairs(&[("v", "a.b")])));
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/c+"), Some(pairs(&[("v", "c+")])));
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/$x"), Some(pairs(&[("v", "$x")])));
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/axb"), None);
+        assert_eq!(captured(pattern.as_str(), Options::default(), "/cc"), None);
+    }
+
+    #[test]
+    fn compile_substitutes_params () {
+        let containers = containers("/users/:id/:tab?");
+
+        assert_eq!(compile(&containers, &params(&[("id", "42"), ("tab", "posts")])).unwrap(), "/users/42/posts");
+        assert_eq!(compile(&containers, &params(&[("id", "42")])).unwrap(), "/users/42");
+        assert_eq!(compile(&containers, &params(&[("tab", "posts")])), Err(CompileError::MissingParameter(String::from("id"))));
+    }
+
+    #[test]
+    fn compile_of_other_tokens () {
+        assert_eq!(compile(&containers("/files/:path+"), &params(&[("path", "a/b")])).unwrap(), "/files/a/b");
+        assert_eq!(compile(&containers("/(?:foo|bar)/:id"), &params(&[("id", "1")])), Err(CompileError::NonCapturingGroup(String::from("foo|bar"))));
+        assert_eq!(compile(&containers("/(?:foo|bar)?/:id"), &params(&[("id", "1")])).unwrap(), "/1");
+    }
+
+    #[test]
+    fn parse_multibyte_literals () {
+        assert_eq!(captured("/café/:id", Options::default(), "/café/1"), Some(pairs(&[("id", "1")])));
+        assert_eq!(captured("/naïve/:x", Options::default(), "/naïve/y"), Some(pairs(&[("x", "y")])));
+        assert_eq!(captured("/路径/:id", Options::default(), "/路径/42"), Some(pairs(&[("id", "42")])));
+        assert_eq!(containers("/路径/:id")[1].token().unwrap().prefix(), "/");
+    }
+
+    #[test]
+    fn parse_multibyte_prefixes () {
+        let options = Options::builder().whitelist(["/", "é"]).build();
+        let containers = parse("/caf:x", options.clone()).unwrap();
+        assert_eq!(containers[1].token().unwrap().prefix(), "");
+
+        let containers = parse("/café:x?", options.clone()).unwrap();
+        assert_eq!(containers.first().unwrap().path(), "/caf");
+        assert_eq!(containers[1].token().unwrap().prefix(), "é");
+        assert_eq!(captured("/café:x?", options, "/caf"), Some(vec![]));
+    }
+
+    fn prefixes (pattern: &str, options: Options) -> Vec<String> {
+        parse(pattern, options).unwrap().iter()
+            .filter_map(|container| container.token())
+            .map(|token| String::from(token.prefix()))
+            .collect()
+    }
+
+    #[test]
+    fn default_whitelist_infers_prefixes () {
+        assert_eq!(prefixes("/:foo", Options::default()), ["/"]);
+        assert_eq!(prefixes("/file.:ext", Options::default()), ["."]);
+        assert_eq!(prefixes("/:a-:b", Options::default()), ["/", ""]);
+        assert_eq!(containers("/:foo").len(), 1);
+    }
+
+    #[test]
+    fn whitelist_limits_prefixes () {
+        let slash = Options::builder().whitelist(["/"]).build();
+
+        assert_eq!(prefixes("/file.:ext", slash.clone()), [""]);
+        assert_eq!(prefixes("/:foo", slash), ["/"]);
+        assert_eq!(prefixes("/:a-:b", Options::builder().whitelist(["-"]).build()), ["", "-"]);
+    }
+
+    #[test]
+    fn multi_character_delimiters () {
+        let options = Options::builder().delimiter("::").build();
+        let containers = parse("foo::bar:::baz", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options.clone()).unwrap();
+
+        assert_eq!(prefixes("foo::bar:::baz", options.clone()), ["::"]);
+        assert_eq!(match_str("foo::bar::x", &regexp, &containers)[0].value(), "x");
+        assert_eq!(match_str("foo::bar::a:b", &regexp, &containers)[0].value(), "a:b");
+        assert!(!is_match("foo::bar::a::b", &regexp));
+        assert!(is_match("foo::bar::x::", &regexp));
+    }
+
+    #[test]
+    fn translate_multi_character_delimiters () {
+        let options = Options::builder().delimiter("::").build();
+        let colons = parse("::users::id::(\\d+)", options.clone()).unwrap();
+        let slashed = translate_delimiter(&colons, "::", "/");
+        let back = translate_delimiter(&slashed, "/", "::");
+
+        assert_eq!(to_regexp_string(&slashed, &Options::default()), to_regexp_string(&containers("/users/id/(\\d+)"), &Options::default()));
+        assert_eq!(to_regexp_string(&back, &options), to_regexp_string(&colons, &options));
+        assert_eq!(compile(&back, &params(&[("0", "7")])).unwrap(), "::users::id::7");
+    }
+
+    #[test]
+    fn root_pattern_matches_root () {
+        let containers = containers("/");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+
+        assert!(is_match("/", &regexp));
+        assert!(!is_match("", &regexp));
+        assert!(!is_match("/a", &regexp));
+        assert!(match_str("/", &regexp, &containers).is_empty());
+    }
+
+    #[test]
+    fn empty_pattern_matches_empty_text () {
+        let containers = containers("");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let prefix = to_regexp(&containers, Options::builder().end(false).build()).unwrap();
+
+        assert!(containers.is_empty());
+        assert!(is_match("", &regexp));
+        assert!(is_match("/", &regexp));
+        assert!(!is_match("/a", &regexp));
+        assert!(is_match("/a", &prefix));
+    }
+
+    #[test]
+    fn absent_optional_params_keep_the_names () {
+        assert_eq!(captured("/:a?/:b", Options::default(), "/y"), Some(pairs(&[("b", "y")])));
+        assert_eq!(captured("/:a?/:b", Options::default(), "/x/y"), Some(pairs(&[("a", "x"), ("b", "y")])));
+        assert_eq!(captured("/:a(\\d+)?/:b/:c?", Options::default(), "/y"), Some(pairs(&[("b", "y")])));
+        assert_eq!(captured("/:a((x)|(y))?/:b", Options::default(), "/z"), Some(pairs(&[("b", "z")])));
+    }
+
+    #[test]
+    fn brace_groups_make_a_prefix_optional () {
+        assert_eq!(captured("/users{/:id}?", Options::default(), "/users"), Some(vec![]));
+        assert_eq!(captured("/users{/:id}?", Options::default(), "/users/42"), Some(pairs(&[("id", "42")])));
+        assert_eq!(captured("/users{/:id}?", Options::default(), "/users42"), None);
+        assert_eq!(prefixes("/users{/:id}?", Options::default()), ["/"]);
+    }
+
+    #[test]
+    fn brace_groups_with_other_prefixes () {
+        assert_eq!(captured("/file{-:v}?", Options::default(), "/file"), Some(vec![]));
+        assert_eq!(captured("/file{-:v}?", Options::default(), "/file-2"), Some(pairs(&[("v", "2")])));
+        assert_eq!(captured("/file{-:v}", Options::default(), "/file"), None);
+    }
+
+    #[test]
+    fn literal_routes_agree_with_the_regexp () {
+        for options in [Options::default(), Options::builder().multi_line(true).build(), Options::builder().strict(true).build()] {
+            let containers = parse("/foo", options.clone()).unwrap();
+            let regexp = to_regexp(&containers, options.clone()).unwrap();
+            let mut router = Router::new(options);
+            router.add("foo", "/foo").unwrap();
+
+            for text in ["/foo", "/foo/", "/FOO", "/foo\nzzz", "zzz\n/foo", "/foo/bar"] {
+                assert_eq!(router.find(text).is_some(), is_match(text, &regexp), "{:?}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn dot_all_lets_wildcards_span_newlines () {
+        let dot_all = Options::builder().dot_all(true).build();
+
+        assert_eq!(captured("/:x(.*)", Options::default(), "/a\nb"), None);
+        assert_eq!(captured("/:x(.*)", dot_all, "/a\nb"), Some(pairs(&[("x", "a\nb")])));
+    }
+
+    #[test]
+    fn flags_are_combined () {
+        let options = Options::builder().multi_line(true).dot_all(true).build();
+        let sensitive = Options::builder().sensitive(true).dot_all(true).build();
+
+        assert!(to_regexp_string(&containers("/foo"), &options).starts_with("(?ims)^"));
+        assert!(to_regexp_string(&containers("/foo"), &sensitive).starts_with("(?s)^"));
+        assert!(to_regexp_string(&containers("/foo"), &Options::default()).starts_with("(?i)^"));
+    }
+
+    #[test]
+    fn compile_partial_escapes_literals () {
+        assert_eq!(compile_partial(&containers("/a\\:b/:id"), &params(&[])), "/a\\:b/:id");
+        assert_eq!(compile_partial(&containers("/a\\(b\\)/:id?"), &params(&[])), "/a\\(b\\)/:id?");
+        assert_eq!(compile_partial(&containers("/a/:x/:id"), &params(&[("x", "v:w")])), "/a/v\\:w/:id");
+        assert_eq!(parse_names(compile_partial(&containers("/a\\:b/:id"), &params(&[])).as_str(), &Options::default()).unwrap(), ["id"]);
+    }
+
+    fn samples (samples: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        samples.iter()
+            .map(|(name, values)| (String::from(*name), values.iter().map(|value| String::from(*value)).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn sample_paths_leave_out_optional_params () {
+        let containers = containers("/a\\:b/:id/:tab?");
+
+        assert_eq!(sample_paths(&containers, &samples(&[("id", &["1", "2"])])).collect::<Vec<String>>(), ["/a:b/1", "/a:b/2"]);
+        assert_eq!(sample_paths(&containers, &samples(&[("id", &["1", "2"]), ("tab", &["x"])])).collect::<Vec<String>>(), ["/a:b/1/x", "/a:b/2/x"]);
+    }
+
+    #[test]
+    fn sample_paths_encode_values_and_skip_invalid_ones () {
+        let containers = containers("/users/:id");
+
+        assert_eq!(sample_paths(&containers, &samples(&[("id", &["a b", "x/y"])])).collect::<Vec<String>>(), ["/users/a%20b"]);
+        assert_eq!(sample_paths(&containers, &samples(&[])).count(), 0);
+    }
+
+    #[test]
+    fn sample_paths_match_their_regexp () {
+        let cases = [
+            ("/users/:id", samples(&[("id", &["a b", "x/y", "42"])])),
+            ("/:id(\\d+)/:tab?", samples(&[("id", &["1", "x"]), ("tab", &["a:b", "c?d"])])),
+            ("/files/:path+", samples(&[("path", &["a/b", "c d/e"])])),
+            ("/a\\(b\\)/:x/:y*", samples(&[("x", &["%", "#"])]))
+        ];
+
+        for (pattern, values) in cases.iter() {
+            let containers = containers(pattern);
+            let regexp = to_regexp(&containers, Options::default()).unwrap();
+            let paths: Vec<String> = sample_paths(&containers, values).collect();
+
+            assert!(!paths.is_empty(), "{}", pattern);
+            for path in paths {
+                assert!(is_match(path.as_str(), &regexp), "{} {}", pattern, path);
+            }
+        }
+    }
+
+    #[test]
+    fn escaped_characters_are_literals () {
+        assert_eq!(containers("/\\:literal")[0].path(), "/:literal");
+        assert_eq!(containers("/path/\\(notgroup\\)")[0].path(), "/path/(notgroup)");
+        assert_eq!(captured("/\\:literal/:id", Options::default(), "/:literal/1"), Some(pairs(&[("id", "1")])));
+        assert_eq!(captured("/path/\\(notgroup\\)", Options::default(), "/path/(notgroup)"), Some(vec![]));
+    }
+
+    #[test]
+    fn repeats_split_at_other_delimiters () {
+        let options = Options::builder().delimiter(".").build();
+        let containers = parse(".:list+", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options.clone()).unwrap();
+        let matches = match_str(".a.b.c", &regexp, &containers);
+
+        assert_eq!(to_regexp_string(&containers, &options), r"(?i)^\.((?:[^.]+?)(?:\.(?:[^.]+?))*)(?:\.)?$");
+        assert_eq!(matches[0].value(), "a.b.c");
+        assert_eq!(matches[0].values(), ["a", "b", "c"]);
+        assert_eq!(match_str(".a/b.c", &regexp, &containers)[0].values(), ["a/b", "c"]);
+        assert!(!is_match(".", &regexp));
+    }
+
+    fn consumed (options: Options, text: &str) -> Option<usize> {
+        let containers = parse("/foo/:bar", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+
+        match_consumed(text, &regexp, &containers).map(|(_, end)| end)
+    }
+
+    #[test]
+    fn strict_and_end_combinations () {
+        let loose = Options::default();
+        let strict = Options::builder().strict(true).build();
+        let prefix = Options::builder().end(false).build();
+        let strict_prefix = Options::builder().strict(true).end(false).build();
+
+        assert_eq!([consumed(loose.clone(), "/foo/x"), consumed(loose.clone(), "/foo/x/"), consumed(loose, "/foo/x/y")], [Some(6), Some(7), None]);
+        assert_eq!([consumed(strict.clone(), "/foo/x"), consumed(strict.clone(), "/foo/x/"), consumed(strict, "/foo/x/y")], [Some(6), None, None]);
+        assert_eq!([consumed(prefix.clone(), "/foo/x"), consumed(prefix.clone(), "/foo/x/"), consumed(prefix, "/foo/x/y")], [Some(6), Some(7), Some(6)]);
+        assert_eq!([consumed(strict_prefix.clone(), "/foo/x"), consumed(strict_prefix.clone(), "/foo/x/"), consumed(strict_prefix, "/foo/x/y")], [Some(6), Some(6), Some(6)]);
+    }
+
+    #[test]
+    fn strict_rejects_trailing_delimiters () {
+        let strict = Options::builder().strict(true).build();
+
+        assert_eq!(captured("/foo/:bar", strict.clone(), "/foo/x"), Some(pairs(&[("bar", "x")])));
+        assert_eq!(captured("/foo/:bar", strict.clone(), "/foo/x/"), None);
+        assert_eq!(captured("/foo/:bar/", strict.clone(), "/foo/x/"), Some(pairs(&[("bar", "x")])));
+        assert_eq!(captured("/foo/:bar/", strict, "/foo/x"), None);
+    }
+
+    #[test]
+    fn wildcard_groups_capture_the_tail () {
+        assert_eq!(captured("/files/(.*)", Options::default(), "/files/a/b/c.txt"), Some(pairs(&[("0", "a/b/c.txt")])));
+        assert_eq!(captured("/files/:path(.*)", Options::default(), "/files/a/b/c.txt"), Some(pairs(&[("path", "a/b/c.txt")])));
+        assert_eq!(captured("/files/:path", Options::default(), "/files/a/b/c.txt"), None);
+        assert_eq!(containers("/files/(.*)")[1].token().unwrap().pattern(), ".*");
+    }
+
+    // Patterns with the source path-to-regexp v6 gives with the same
+    // options and the source of `to_regexp_string` without the flags.
+    fn v6_parity () -> Vec<(&'static str, Options, &'static str, &'static str)> {
+        let default = Options::default();
+
+        vec![
+            // Required params are not wrapped in a group of their own, and the
+            // default pattern only excludes `/` where v6 excludes `/#?`.
+            ("/users/:id", default.clone(), r"^\/users(?:\/([^\/#\?]+?))[\/#\?]?$", r"^\/users\/([^/]+?)(?:\/)?$"),
+            ("/users/:id?", default.clone(), r"^\/users(?:\/([^\/#\?]+?))?[\/#\?]?$", r"^\/users(?:\/([^/]+?))?(?:\/)?$"),
+            ("/users/:id(\\d+)", default.clone(), r"^\/users(?:\/(\d+))[\/#\?]?$", r"^\/users\/(\d+)(?:\/)?$"),
+            ("/users/:id(\\d+)?", default.clone(), r"^\/users(?:\/(\d+))?[\/#\?]?$", r"^\/users(?:\/(\d+))?(?:\/)?$"),
+            ("/files/:path+", default.clone(), r"^\/files(?:\/((?:[^\/#\?]+?)(?:\/(?:[^\/#\?]+?))*))[\/#\?]?$", r"^\/files\/((?:[^/]+?)(?:\/(?:[^/]+?))*)(?:\/)?$"),
+            ("/files/:path*", default.clone(), r"^\/files(?:\/((?:[^\/#\?]+?)(?:\/(?:[^\/#\?]+?))*))?[\/#\?]?$", r"^\/files(?:\/((?:[^/]+?)(?:\/(?:[^/]+?))*))?(?:\/)?$"),
+            ("/(.+)", default.clone(), r"^(?:\/(.+))[\/#\?]?$", r"^\/(.+)(?:\/)?$"),
+            ("/:a/(\\d+)", default.clone(), r"^(?:\/([^\/#\?]+?))(?:\/(\d+))[\/#\?]?$", r"^\/([^/]+?)\/(\d+)(?:\/)?$"),
+            ("/users{/:id}?", default.clone(), r"^\/users(?:\/([^\/#\?]+?))?[\/#\?]?$", r"^\/users(?:\/([^/]+?))?(?:\/)?$"),
+            // The default pattern excludes the `.` prefix too, see below.
+            ("/route.:ext", default.clone(), r"^\/route(?:\.([^\/#\?]+?))[\/#\?]?$", r"^\/route\.([^./]+?)(?:\/)?$"),
+            ("/about", default.clone(), r"^\/about[\/#\?]?$", r"^\/about(?:\/)?$"),
+            ("/", default, r"^\/[\/#\?]?$", r"^\/(?:\/)?$"),
+            ("/users/:id", Options::builder().strict(true).build(), r"^\/users(?:\/([^\/#\?]+?))$", r"^\/users\/([^/]+?)$"),
+            // Lookaheads of several alternatives are split, as `fancy_regex`
+            // mismatches `(?=a|b)`.
+            ("/api", Options::builder().end(false).build(), r"^\/api(?:[\/#\?](?=[]|$))?(?=[\/#\?]|[]|$)", r"^\/api(?:\/(?=$))?(?:(?=\/)|(?=$))"),
+            (":a.:b", Options::builder().delimiter(".").build(), r"^([^\.]+?)(?:\.([^\.]+?))[\.]?$", r"^([^.]+?)\.([^.]+?)(?:\.)?$"),
+            ("/Foo", Options::builder().sensitive(true).build(), r"^\/Foo[\/#\?]?$", r"^\/Foo(?:\/)?$")
+        ]
+    }
+
+    /**
+     * Rewrite a v6 source for `fancy_regex`, which rejects `\/` and empty
+     * classes and mismatches lookaheads of several alternatives.
+     */
+    fn v6_to_fancy (source: &str, options: &Options) -> FancyRegex {
+        let source = source
+            .replace(r"(?=[\/#\?]|[]|$)", r"(?:(?=[/#?])|(?=$))")
+            .replace(r"(?=[]|$)", r"(?=$)")
+            .replace(r"[\/#\?]", r"[/#?]")
+            .replace(r"[^\/#\?]", r"[^/#?]")
+            .replace(r"[\.]", r"[.]")
+            .replace(r"[^\.]", r"[^.]");
+
+        FancyRegex::new(flags(source.as_str(), options).as_str()).unwrap()
+    }
+
+    #[test]
+    fn regexp_sources_of_path_to_regexp_v6 () {
+        for (pattern, options, _, source) in v6_parity() {
+            let containers = parse(pattern, options.clone()).unwrap();
+
+            assert_eq!(flags(source, &options), to_regexp_string(&containers, &options), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn regexps_match_like_path_to_regexp_v6 () {
+        let texts = [
+            "/", "//", "/users", "/users/", "/users/42", "/users/42/", "/users/4/2", "/USERS/42",
+            "/files", "/files/", "/files/a", "/files/a/b/", "/about", "/about/", "/About",
+            "/route.json", "/route.", "/api", "/api/", "/api/users", "/apiv2", "/foo", "/Foo",
+            "/x/12", "/x/ab", "a.b", "a.b.", "a.", "", "x"
+        ];
+
+        for (pattern, options, v6, _) in v6_parity() {
+            let containers = parse(pattern, options.clone()).unwrap();
+            let regexp = to_regexp(&containers, options.clone()).unwrap();
+            let expected = v6_to_fancy(v6, &options);
+
+            for text in texts {
+                assert_eq!(is_match(text, &regexp), is_match(text, &expected), "{} {:?}", pattern, text);
+            }
+        }
+    }
+
+    #[test]
+    fn regexps_diverging_from_path_to_regexp_v6 () {
+        let default = Options::default();
+        let users = to_regexp(&containers("/users/:id"), default.clone()).unwrap();
+        let route = to_regexp(&containers("/route.:ext"), default.clone()).unwrap();
+
+        // v6 ends a param at `#` and `?` too.
+        let v6_users = v6_to_fancy(r"^\/users(?:\/([^\/#\?]+?))[\/#\?]?$", &default);
+        assert_eq!(raw_captures(&users, "/users/42#").unwrap().at(1), Some("42#"));
+        assert_eq!(raw_captures(&v6_users, "/users/42#").unwrap().at(1), Some("42"));
+        // The default pattern excludes the `.` prefix of the param.
+        assert!(!is_match("/route.tar.gz", &route));
+        assert!(is_match("/route.tar.gz", &v6_to_fancy(r"^\/route(?:\.([^\/#\?]+?))[\/#\?]?$", &default)));
+        // A required group must not match an empty value.
+        assert!(!is_match("/", &to_regexp(&containers("/(.*)"), default.clone()).unwrap()));
+        assert!(is_match("/", &v6_to_fancy(r"^(?:\/(.*))[\/#\?]?$", &default)));
+    }
+
+    #[test]
+    fn optional_params_keep_their_prefix () {
+        let containers = containers("/users/:id?");
+
+        assert_eq!(to_regexp_string(&containers, &Options::default()), r"(?i)^\/users(?:\/([^/]+?))?(?:\/)?$");
+        assert_eq!(captured("/users/:id?", Options::default(), "/users"), Some(vec![]));
+        assert_eq!(captured("/users/:id?", Options::default(), "/users/"), Some(vec![]));
+        assert_eq!(captured("/users/:id?", Options::default(), "/users/5"), Some(pairs(&[("id", "5")])));
+        assert_eq!(captured("/users/:id?", Options::default(), "/users5"), None);
+        assert_eq!(captured("/users/:id(\\d+)?", Options::default(), "/users/x"), None);
+    }
+
+    #[test]
+    fn case_insensitive_ascii () {
+        assert_eq!(captured("/Foo/:id", Options::default(), "/foo/1"), Some(pairs(&[("id", "1")])));
+        assert_eq!(captured("/foo/:id(a+)", Options::default(), "/FOO/AaA"), Some(pairs(&[("id", "AaA")])));
+        assert_eq!(captured("/Foo/:id", Options::builder().sensitive(true).build(), "/foo/1"), None);
+    }
+
+    #[test]
+    fn case_insensitive_unicode () {
+        assert_eq!(captured("/ÜBER", Options::default(), "/über"), Some(vec![]));
+        assert_eq!(captured("/ΣΑ", Options::default(), "/ςα"), Some(vec![]));
+        // Only simple case folding, a character never matches several.
+        assert_eq!(captured("/Straße", Options::default(), "/STRASSE"), None);
+        assert_eq!(captured("/Straße", Options::default(), "/STRAẞE"), Some(vec![]));
+    }
+
+    #[test]
+    fn prefix_matches_stop_at_segments () {
+        let prefix = Options::builder().end(false).build();
+        let api = to_regexp(&containers("/api"), prefix.clone()).unwrap();
+
+        assert!(is_match("/api", &api));
+        assert!(is_match("/api/", &api));
+        assert!(is_match("/api/users", &api));
+        assert!(!is_match("/apiv2", &api));
+        assert!(!is_match("/ap", &api));
+        assert_eq!(captured("/api/:v", prefix.clone(), "/api/v1/users"), Some(pairs(&[("v", "v1")])));
+        assert_eq!(captured("/api/", prefix, "/api/v2"), Some(vec![]));
+    }
+
+    #[test]
+    fn prefix_matches_after_multibyte_literals () {
+        let prefix = Options::builder().end(false).build();
+        let regexp = to_regexp(&containers("/café"), prefix.clone()).unwrap();
+        let delimited = to_regexp(&containers("/路径/"), prefix).unwrap();
+
+        assert!(is_match("/café/x", &regexp));
+        assert!(!is_match("/cafés", &regexp));
+        assert!(is_match("/路径/x", &delimited));
+    }
+
+    #[test]
+    fn ends_with_leaves_the_query_to_the_rest () {
+        let options = Options::builder().ends_with(vec![String::from("?"), String::from("#")]).build();
+        let containers = parse("/users/:id", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+        let (matches, rest) = match_prefix("/users/5?x=1", &regexp, &containers).unwrap();
+
+        assert_eq!(match_str("/users/5?x=1", &regexp, &containers)[0].value(), "5");
+        assert_eq!(matches[0].value(), "5");
+        assert_eq!(rest, "?x=1");
+        assert_eq!(match_consumed("/users/5/#top", &regexp, &containers).map(|(_, end)| end), Some(9));
+    }
+
+    #[test]
+    fn ends_with_escapes_terminators () {
+        let options = Options::builder().ends_with([".", "|"]).build();
+        let containers = parse("/users/:id(\\d+)", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+
+        assert!(is_match("/users/5.json", &regexp));
+        assert!(is_match("/users/5|x", &regexp));
+        assert!(!is_match("/users/5x", &regexp));
+    }
+
+    #[test]
+    fn match_consumed_mounts_prefixes () {
+        let prefix = Options::builder().end(false).build();
+        let admin = containers("/admin");
+        let regexp = to_regexp(&admin, prefix.clone()).unwrap();
+        let (_, end) = match_consumed("/admin/users/3", &regexp, &admin).unwrap();
+
+        assert_eq!(end, 6);
+        let users = containers("/users/:id");
+        let mounted = to_regexp(&users, Options::default()).unwrap();
+        assert_eq!(match_str(&"/admin/users/3"[end..], &mounted, &users)[0].value(), "3");
+        assert!(match_consumed("/other", &regexp, &admin).is_none());
+    }
+
+    #[test]
+    fn match_consumed_of_full_matches () {
+        let containers = containers("/users/:id");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let (matches, end) = match_consumed("/users/3/", &regexp, &containers).unwrap();
+
+        assert_eq!((matches[0].value(), end), ("3", 9));
+    }
+
+    #[test]
+    fn adjacent_params_need_a_delimiter () {
+        assert_eq!(parse("/:a:b", Options::default()).err(), Some(PathError::MissingDelimiter { index: 3 }));
+        assert_eq!(parse("/:year:month:day", Options::default()).err(), Some(PathError::MissingDelimiter { index: 6 }));
+        assert_eq!(parse("/:a(.*):b", Options::default()).map(|containers| containers.len()).ok(), Some(2));
+    }
+
+    #[test]
+    fn adjacent_params_with_custom_patterns () {
+        assert_eq!(captured("/:a(\\d+):b", Options::default(), "/12ab"), Some(pairs(&[("a", "12"), ("b", "ab")])));
+        assert_eq!(captured("/:a-:b", Options::default(), "/x-y"), Some(pairs(&[("a", "x"), ("b", "y")])));
+    }
+
+    #[test]
+    fn trailing_optional_segments_collapse () {
+        assert_eq!(captured("/:foo/:bar?", Options::default(), "/x"), Some(pairs(&[("foo", "x")])));
+        assert_eq!(captured("/:foo/:bar?", Options::default(), "/x/"), Some(pairs(&[("foo", "x")])));
+        assert_eq!(captured("/:foo/:bar?", Options::default(), "/x/y"), Some(pairs(&[("foo", "x"), ("bar", "y")])));
+        assert_eq!(captured("/:foo/:bar?", Options::default(), "/x//"), None);
+        assert_eq!(captured("/:foo/:bar?", Options::default(), "/x/y/z"), None);
+    }
+
+    fn param (pattern: &str, options: Options, text: &str, name: &str) -> Option<String> {
+        let containers = parse(pattern, options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+
+        match_param(text, &regexp, &containers, name)
+    }
+
+    #[test]
+    fn match_param_finds_the_named_value () {
+        let pattern = "/(?:a|b)/:x((\\d)+)/:opt?/(c)/:id";
+
+        assert_eq!(param(pattern, Options::default(), "/a/12/c/42", "id"), Some(String::from("42")));
+        assert_eq!(param(pattern, Options::default(), "/b/12/o/c/42", "id"), Some(String::from("42")));
+        assert_eq!(param(pattern, Options::default(), "/b/12/o/c/42", "x"), Some(String::from("12")));
+        assert_eq!(param(pattern, Options::default(), "/b/12/o/c/42", "0"), Some(String::from("c")));
+        assert_eq!(param(pattern, Options::default(), "/a/12/c/42", "opt"), None);
+        assert_eq!(param(pattern, Options::default(), "/a/12/c/42", "missing"), None);
+        assert_eq!(param(pattern, Options::default(), "/x/12/c/42", "id"), None);
+    }
+
+    #[test]
+    fn match_param_decodes_like_match_values () {
+        let options = Options::builder().decode(true).build();
+        let containers = parse("/:name/:path*", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options.clone()).unwrap();
+        let matches = match_str("/a%20b/c%2Fd/e", &regexp, &containers);
+
+        assert_eq!(param("/:name/:path*", options.clone(), "/a%20b/c%2Fd/e", "name").as_deref(), Some(matches[0].value()));
+        assert_eq!(param("/:name/:path*", options, "/a%20b/c%2Fd/e", "path").as_deref(), Some(matches[1].value()));
+        assert_eq!(param("/:name", Options::default(), "/a%20b", "name"), Some(String::from("a%20b")));
+    }
+
+    #[test]
+    fn empty_whitelist_accepts_the_delimiter_and_dot () {
+        let empty = Options::builder().whitelist(Vec::<String>::new()).build();
+
+        assert_eq!(prefixes("/:a", empty.clone()), ["/"]);
+        assert_eq!(prefixes("/file.:ext", empty.clone()), ["."]);
+        assert_eq!(prefixes("/a-:b", empty), [""]);
+        assert_eq!(prefixes("foo:::a", Options::builder().delimiter("::").build()), ["::"]);
+    }
+
+    #[test]
+    fn explicit_whitelist_narrows_prefixes () {
+        let dot = Options::builder().whitelist(["."]).build();
+
+        assert_eq!(prefixes("/:a", dot.clone()), [""]);
+        assert_eq!(prefixes("/file.:ext", dot.clone()), ["."]);
+        assert_eq!(captured("/file.:ext?", dot, "/file"), Some(vec![]));
+    }
+
+    fn joined (base: &str, suffix: &str, options: Options) -> Result<Vec<Container>, PathError> {
+        concat(&parse(base, options.clone()).unwrap(), &parse(suffix, options.clone()).unwrap(), &options)
+    }
+
+    #[test]
+    fn concat_equals_parsing_the_joined_pattern () {
+        let dot = Options::builder().whitelist(["/"]).build();
+
+        for (base, suffix, options) in [
+            ("/api", "/users/:id", Options::default()),
+            ("/api/", ":id?", Options::default()),
+            ("/file", ".:ext", Options::default()),
+            ("/file.", ":ext", dot),
+            ("/(a)", "/(b)/:id", Options::default()),
+            ("/:a", "-:b", Options::default()),
+            ("", "/:id", Options::default()),
+            ("/", ":id", Options::default())
+        ] {
+            let containers = joined(base, suffix, options.clone()).unwrap();
+            let expected = parse(format!("{}{}", base, suffix).as_str(), options.clone()).unwrap();
+
+            assert_eq!(to_regexp_string(&containers, &options), to_regexp_string(&expected, &options), "{} {}", base, suffix);
+            assert_eq!(to_js_pattern(&containers), to_js_pattern(&expected));
+            assert_eq!(containers.len(), expected.len());
+        }
+    }
+
+    #[test]
+    fn concat_numbers_only_unnamed_groups () {
+        let containers = joined("/(a)/:5", "/(b)/:7", Options::default()).unwrap();
+        let names: Vec<&str> = containers.iter().filter_map(|c| c.token()).map(|t| t.name()).collect();
+
+        assert_eq!(names, ["0", "5", "1", "7"]);
+        assert_eq!(compile_partial(&containers, &params(&[])), "/(a)/:5/(b)/:7");
+    }
+
+    #[test]
+    fn concat_rejects_duplicate_params () {
+        assert_eq!(joined("/:id", "/:id", Options::default()).err(), Some(PathError::DuplicateParameter(String::from("id"))));
+        assert_eq!(joined("/(a)", "/:1/(b)", Options::default()).err(), Some(PathError::DuplicateParameter(String::from("1"))));
+    }
+
+    #[test]
+    fn default_pattern_replaces_the_computed_one () {
+        let words = Options::builder().default_pattern(r"\w+").build();
+        let containers = parse("/:path+", words.clone()).unwrap();
+
+        assert_eq!(to_regexp_string(&containers, &words), r"(?i)^\/((?:\w+)(?:\/(?:\w+))*)(?:\/)?$");
+        assert_eq!(captured("/:path+", words.clone(), "/a/b_c"), Some(pairs(&[("path", "a/b_c")])));
+        assert_eq!(captured("/:path+", words.clone(), "/a/b-c"), None);
+        assert_eq!(captured("/:id/:tab?", words.clone(), "/42"), Some(pairs(&[("id", "42")])));
+        assert_eq!(captured("/:id(\\d+)", words, "/42"), Some(pairs(&[("id", "42")])));
+    }
+
+    #[test]
+    fn default_pattern_keeps_custom_groups () {
+        let words = Options::builder().default_pattern(r"\w+").build();
+
+        assert_eq!(captured("/:id(.*)", words.clone(), "/a-b/c"), Some(pairs(&[("id", "a-b/c")])));
+        assert_eq!(parse("/:a:b(-x)", words).err(), Some(PathError::MissingDelimiter { index: 3 }));
+    }
+
+    #[test]
+    fn captures_may_span_the_whole_text () {
+        let containers = containers(":all");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let matches = match_str("everything", &regexp, &containers);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].name(), matches[0].value(), matches[0].start(), matches[0].end()), ("all", "everything", 0, 10));
+        assert_eq!(captured("(.*)", Options::default(), "a/b"), Some(pairs(&[("0", "a/b")])));
+    }
+
+    fn matches_of (pattern: &str, text: &str) -> Vec<Match> {
+        let containers = containers(pattern);
+        let regexp = to_regexp(&containers, Options::builder().end(false).build()).unwrap();
+
+        match_str(text, &regexp, &containers)
+    }
+
+    fn names_and_values (matches: &[Match]) -> Vec<(String, String)> {
+        matches.iter().map(|m| (String::from(m.name()), String::from(m.value()))).collect()
+    }
+
+    #[test]
+    fn merge_matches_of_disjoint_names () {
+        let merged = merge_matches(matches_of("/api/:version", "/api/v1"), matches_of("/users/:id", "/users/42")).unwrap();
+
+        assert_eq!(names_and_values(&merged), pairs(&[("version", "v1"), ("id", "42")]));
+    }
+
+    #[test]
+    fn merge_matches_keeps_a_duplicate_once () {
+        let merged = merge_matches(matches_of("/:id/:tab", "/42/posts"), matches_of("/:id", "/42")).unwrap();
+
+        assert_eq!(names_and_values(&merged), pairs(&[("id", "42"), ("tab", "posts")]));
+    }
+
+    #[test]
+    fn merge_matches_of_conflicting_values () {
+        let conflict = merge_matches(matches_of("/:id", "/1"), matches_of("/:id", "/2")).unwrap_err();
+
+        assert_eq!((conflict.name(), conflict.outer(), conflict.inner()), ("id", "1", "2"));
+    }
+
+    #[test]
+    #[should_panic(expected = "min 5 is greater than max 4")]
+    fn int_range_panics_on_a_reversed_range () {
+        patterns::int_range(5, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "no values given")]
+    fn one_of_panics_without_values () {
+        patterns::one_of(&[]);
+    }
+
+    #[test]
+    fn capture_count_of_params_and_groups () {
+        assert_eq!(capture_count(&containers("/users/:id/(.*)")), 2);
+        assert_eq!(capture_count(&containers("/at/:coord((\\d+),(\\d+))")), 3);
+        assert_eq!(capture_count(&containers("/a/(?:b|c)")), 0);
+    }
+
+    #[test]
+    fn match_checked_of_corresponding_containers () {
+        let containers = containers("/users/:id");
+        let regexp = to_regexp(&containers, Options::default()).unwrap();
+        let matches = match_checked("/users/42", &regexp, &containers).unwrap();
+
+        assert_eq!(names_and_values(&matches), pairs(&[("id", "42")]));
+    }
+
+    #[test]
+    fn match_checked_of_other_containers () {
+        let regexp = to_regexp(&containers("/:a/:b"), Options::default()).unwrap();
+        let mismatch = match_checked("/x/y", &regexp, &containers("/:a")).unwrap_err();
+
+        assert_eq!((mismatch.expected(), mismatch.found()), (1, 2));
+    }
+
+    #[test]
+    fn estimated_cost_orders_patterns () {
+        let literal = estimated_cost(&containers("/users/all"));
+        let param = estimated_cost(&containers("/users/:id"));
+        let wildcard = estimated_cost(&containers("/users/:id/(.*)"));
+        let repeat = estimated_cost(&containers("/users/:path+"));
+
+        assert_eq!(literal, 0);
+        assert!(literal < param);
+        assert!(param < wildcard);
+        assert!(param < repeat);
+    }
+
+    #[test]
+    fn compile_with_an_identity_encoder () {
+        let identity = |value: &str| String::from(value);
+
+        assert_eq!(compile_with_encoder(&containers("/users/:id"), &params(&[("id", "a%20b")]), false, identity).unwrap(), "/users/a%20b");
+        assert_eq!(compile_with_encoder(&containers("/files/:path+"), &params(&[("path", "a b/c")]), false, identity).unwrap(), "/files/a b/c");
+        assert_eq!(compile(&containers("/users/:id"), &params(&[("id", "a%20b")])).unwrap(), "/users/a%2520b");
+    }
+
+    #[test]
+    fn regexp_to_regexp_without_groups () {
+        let (regexp, containers) = regexp_to_regexp("^/users/\\d+$").unwrap();
+
+        assert!(containers.is_empty());
+        assert!(is_match("/users/42", &regexp));
+        assert!(match_str("/users/42", &regexp, &containers).is_empty());
+    }
+
+    #[test]
+    fn regexp_to_regexp_of_nested_groups () {
+        let (regexp, containers) = regexp_to_regexp("^/at/((\\d+),(\\d+))$").unwrap();
+        let matches = match_str("/at/3,4", &regexp, &containers);
+
+        assert_eq!(names_and_values(&matches), pairs(&[("0", "3,4"), ("1", "3"), ("2", "4")]));
+    }
+
+    #[test]
+    fn regexp_to_regexp_skips_non_capturing_groups () {
+        let (regexp, containers) = regexp_to_regexp("^/(?:users|posts)/(\\d+)(?=/|$)").unwrap();
+        let matches = match_str("/posts/7", &regexp, &containers);
+
+        assert_eq!(names_and_values(&matches), pairs(&[("0", "7")]));
+    }
+
+    #[test]
+    fn count_groups_of_named_groups_and_lookarounds () {
+        assert_eq!(count_groups("(?P<a>x)(y)"), 2);
+        assert_eq!(count_groups("(?<a>x)(?<=x)(?<!y)"), 1);
+        assert_eq!(count_groups("(?:x)(?=y)(?!z)[(]\\(()"), 1);
+    }
+
+    #[test]
+    fn array_to_regexp_matches_every_alternative () {
+        let patterns = ["/users/:id", "/accounts/:id(\\d+)/:tab?", "/posts/:slug"];
+        let (regexp, containers) = array_to_regexp(&patterns, Options::default()).unwrap();
+
+        assert_eq!(names_and_values(&match_str("/users/u1", &regexp, &containers)), pairs(&[("id", "u1")]));
+        assert_eq!(names_and_values(&match_str("/accounts/7", &regexp, &containers)), pairs(&[("id", "7")]));
+        assert_eq!(names_and_values(&match_str("/accounts/7/billing", &regexp, &containers)), pairs(&[("id", "7"), ("tab", "billing")]));
+        assert_eq!(names_and_values(&match_str("/posts/hello", &regexp, &containers)), pairs(&[("slug", "hello")]));
+        assert!(!is_match("/accounts/x", &regexp));
+    }
+
+    #[test]
+    fn parse_reports_unbalanced_groups () {
+        assert_eq!(parse("/:id((\\d+)", Options::default()).err(), Some(PathError::UnbalancedGroup { index: 4 }));
+        assert_eq!(parse("/a)", Options::default()).err(), Some(PathError::UnbalancedGroup { index: 2 }));
+        assert_eq!(parse("/:id(\\d+))", Options::default()).err(), Some(PathError::UnbalancedGroup { index: 9 }));
+    }
+
+    #[test]
+    fn engine_errors_are_no_match () {
+        let options = Options::builder().end(false).build();
+        let containers = parse("/:x((?:a|b)*)c", options.clone()).unwrap();
+        let regexp = to_regexp(&containers, options).unwrap();
+        let text = format!("/{}", "a".repeat(2_000_000));
+
+        assert!(try_is_match(text.as_str(), &regexp).is_err());
+        assert!(!is_match(text.as_str(), &regexp));
+        assert!(match_str(text.as_str(), &regexp, &containers).is_empty());
+        assert!(match_param(text.as_str(), &regexp, &containers, "x").is_none());
+        assert!(raw_captures(&regexp, text.as_str()).is_none());
+    }
+
+    #[test]
+    fn parse_rejects_duplicate_names () {
+        assert_eq!(parse("/:id/:id", Options::default()).err(), Some(PathError::DuplicateParameter(String::from("id"))));
+        assert_eq!(parse_names("/:id/:ID", &Options::default()).unwrap(), ["id", "ID"]);
+    }
+
+    #[test]
+    fn compile_with_validates_values () {
+        let containers = containers("/users/:id(\\d+)");
+
+        assert_eq!(compile_with(&containers, &params(&[("id", "42")]), true), Ok(String::from("/users/42")));
+        assert_eq!(compile_with(&containers, &params(&[("id", "abc")]), true), Err(CompileError::InvalidParameterValue {
+            name: String::from("id"),
+            value: String::from("abc")
+        }));
+        assert_eq!(compile_with(&containers, &params(&[("id", "abc")]), false), Ok(String::from("/users/abc")));
+    }
+
+    #[test]
+    fn compile_with_of_required_non_capturing_groups () {
+        let containers = containers("/(?:foo|bar)/:id");
+
+        assert_eq!(compile_with(&containers, &params(&[("id", "1")]), true), Err(CompileError::NonCapturingGroup(String::from("foo|bar"))));
+    }
+
+    #[test]
+    fn parse_rejects_names_of_unnamed_groups () {
+        assert_eq!(parse("/:0/(\\d+)", Options::default()).err(), Some(PathError::DuplicateParameter(String::from("0"))));
+        assert_eq!(parse("/(\\d+)/:0", Options::default()).err(), Some(PathError::DuplicateParameter(String::from("0"))));
+        assert_eq!(parse_names("/:1/(\\d+)", &Options::default()).unwrap(), ["1", "0"]);
+    }
+
+    #[test]
+    fn to_regexp_fast_matches_like_to_regexp () {
+        let texts = ["/users", "/users/", "/users/42", "/users/42/", "/USERS/42", "/users/42/posts", "/users/4%2F2", ""];
+
+        for (pattern, options) in [
+            ("/users/:id", Options::default()),
+            ("/users/:id?", Options::default()),
+            ("/users/:id", Options::builder().strict(true).build()),
+            ("/users/:id(\\d+)/:tab?", Options::builder().sensitive(true).build()),
+            ("/users/", Options::builder().end(false).strict(true).build())
+        ] {
+            let containers = parse(pattern, options.clone()).unwrap();
+            let regexp = to_regexp(&containers, options.clone()).unwrap();
+            let fast = to_regexp_fast(&containers, options).unwrap();
+
+            for text in texts {
+                assert_eq!(fast.is_match(text), is_match(text, &regexp), "{} {:?}", pattern, text);
+            }
+        }
+    }
+
+    #[test]
+    fn to_regexp_fast_rejects_lookarounds () {
+        let prefix = Options::builder().end(false).build();
+
+        assert!(matches!(to_regexp_fast(&parse("/api", prefix.clone()).unwrap(), prefix), Err(PathError::InvalidRegex { .. })));
+        assert!(matches!(to_regexp_fast(&containers("/:id((?=\\d)\\w+)"), Options::default()), Err(PathError::InvalidRegex { .. })));
+        assert!(matches!(to_regexp_fast(&containers("/:x(.*)"), Options::default()), Err(PathError::InvalidRegex { .. })));
+        assert!(to_regexp_fast(&containers("/:x(.*)?"), Options::default()).is_ok());
+    }
+}
@@ -0,0 +1,139 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use fancy_regex::Regex as FancyRegex;
+
+use super::{Container, Match, Options, PathError, TokenCapture, parse, to_regexp, token_captures, token_regex};
+
+/**
+ * A pattern parsed and compiled once, to match any number of texts.
+ */
+pub struct PathMatcher {
+    containers: Vec<Container>,
+    regexp: FancyRegex,
+    // The regexp of each param value, compiled the first time it is asked for.
+    params: Vec<OnceLock<Result<FancyRegex, PathError>>>
+}
+
+impl PathMatcher {
+    /**
+     * Parse the pattern and compile its regexp.
+     *
+     * @param  {&str} pattern
+     * @param  {Options} options
+     * @return {Result<PathMatcher, PathError>}
+     */
+    pub fn new (pattern: &str, options: Options) -> Result<PathMatcher, PathError> {
+        let containers = parse(pattern, options.clone())?;
+        let regexp = to_regexp(&containers, options)?;
+        let params = containers.iter()
+            .filter_map(Container::token)
+            .map(|_| OnceLock::new())
+            .collect();
+
+        Ok(PathMatcher {
+            containers,
+            regexp,
+            params
+        })
+    }
+
+    /**
+     * Tell whether the text matches, exactly when `captures` is not `None`.
+     *
+     * @param  {&str} text
+     * @return {bool}
+     */
+    pub fn matches (&self, text: &str) -> bool {
+        token_captures(text, &self.regexp, &self.containers).is_some()
+    }
+
+    /**
+     * Match the text, returning `None` when it does not match.
+     *
+     * @param  {&str} text
+     * @return {Option<Vec<Match>>}
+     */
+    pub fn captures (&self, text: &str) -> Option<Vec<Match>> {
+        token_captures(text, &self.regexp, &self.containers)
+            .map(|captures| captures.into_iter().map(TokenCapture::into_match).collect())
+    }
+
+    /**
+     * Get the anchored regexp of a param value, like `param_regex`, compiled
+     * once and kept for the next calls.
+     *
+     * @param  {&str} name
+     * @return {Result<Option<&FancyRegex>, PathError>}
+     */
+    pub fn param_regex (&self, name: &str) -> Result<Option<&FancyRegex>, PathError> {
+        let found = self.containers.iter()
+            .filter_map(Container::token)
+            .zip(self.params.iter())
+            .find(|(token, _)| token.name == name);
+
+        match found {
+            Some((token, regexp)) => regexp.get_or_init(|| token_regex(token)).as_ref().map(Some).map_err(Clone::clone),
+            None => Ok(None)
+        }
+    }
+
+    pub fn containers (&self) -> &[Container] {
+        self.containers.as_slice()
+    }
+
+    pub fn regexp (&self) -> &FancyRegex {
+        &self.regexp
+    }
+}
+
+impl FromStr for PathMatcher {
+    type Err = PathError;
+
+    /**
+     * Parse the pattern with the default options, for
+     * `let matcher: PathMatcher = "/users/:id".parse()?`.
+     */
+    fn from_str (pattern: &str) -> Result<PathMatcher, PathError> {
+        PathMatcher::new(pattern, Options::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names_and_values (matches: &[Match]) -> Vec<(&str, &str)> {
+        matches.iter().map(|m| (m.name(), m.value())).collect()
+    }
+
+    #[test]
+    fn matcher_captures_a_matching_path () {
+        let matcher = PathMatcher::new("/users/:id", Options::default()).unwrap();
+
+        assert!(matcher.matches("/users/42"));
+        assert_eq!(names_and_values(&matcher.captures("/users/42").unwrap()), [("id", "42")]);
+    }
+
+    #[test]
+    fn matcher_rejects_other_paths () {
+        let matcher = PathMatcher::new("/users/:id", Options::default()).unwrap();
+
+        assert!(!matcher.matches("/posts/42"));
+        assert!(matcher.captures("/users").is_none());
+    }
+
+    #[test]
+    fn matcher_of_an_invalid_pattern () {
+        assert!(matches!(PathMatcher::new("/:id((\\d+)", Options::default()), Err(PathError::UnbalancedGroup { .. })));
+        assert!(matches!(PathMatcher::new("/:id([)", Options::default()), Err(PathError::InvalidRegex { .. })));
+    }
+
+    #[test]
+    fn matcher_from_str () {
+        let matcher: PathMatcher = "/users/:id".parse().unwrap();
+
+        assert_eq!(names_and_values(&matcher.captures("/USERS/42").unwrap()), [("id", "42")]);
+        assert!(matches!("/users/:id(".parse::<PathMatcher>(), Err(PathError::UnbalancedGroup { .. })));
+    }
+}
@@ -0,0 +1,137 @@
+//! Ready-made patterns to use as custom capture groups, e.g.
+//! `format!("/:n({})", patterns::int_range(1, 255))`.
+
+/**
+ * Get a pattern matching the integers from `min` to `max` (both included)
+ * without leading zeros, like `25[0-5]|2[0-4]\d|1\d\d|[1-9]\d|\d` for
+ * 0 to 255. Longer numbers come first so none is matched partially.
+ *
+ * # Panics
+ *
+ * When `min` is greater than `max`.
+ *
+ * @param  {u32} min
+ * @param  {u32} max
+ * @return {String}
+ */
+pub fn int_range (min: u32, max: u32) -> String {
+    assert!(min <= max, "int_range: min {} is greater than max {}", min, max);
+
+    let mut alternatives: Vec<String> = split_range(u64::from(min), u64::from(max)).iter()
+        .map(|&(lo, hi)| digits_range(lo, hi))
+        .collect();
+    alternatives.reverse();
+
+    alternatives.join("|")
+}
+
+/**
+ * Get a pattern matching exactly one of the values, e.g. `GET|POST|PUT`
+ * for `format!("/:method({})", patterns::one_of(&["GET", "POST", "PUT"]))`.
+ * Special characters of the values are escaped, and longer values come
+ * first so none is matched partially.
+ *
+ * # Panics
+ *
+ * When `values` is empty.
+ *
+ * @param  {&[&str]} values
+ * @return {String}
+ */
+pub fn one_of (values: &[&str]) -> String {
+    assert!(!values.is_empty(), "one_of: no values given");
+
+    let mut alternatives: Vec<String> = values.iter()
+        .map(|value| escape_value(value))
+        .collect();
+    alternatives.sort_by_key(|alternative| std::cmp::Reverse(alternative.len()));
+
+    alternatives.join("|")
+}
+
+/**
+ * Escape the special characters of a value for a capture group. A `$` is
+ * written as `\x24`, as `parse` escapes a `$` of the group once more.
+ *
+ * @param  {&str} value
+ * @return {String}
+ */
+fn escape_value (value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '$' => escaped.push_str(r"\x24"),
+            '\\' | '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            _ => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+/**
+ * Split a range into ranges whose bounds have the same number of digits
+ * and only differ by a single digit followed by any digits.
+ *
+ * @param  {u64} min
+ * @param  {u64} max
+ * @return {Vec<(u64, u64)>}
+ */
+fn split_range (min: u64, max: u64) -> Vec<(u64, u64)> {
+    let mut stops: Vec<u64> = vec![max];
+    let mut power = 10;
+
+    // Fill the trailing digits of min with nines: 123 => 129, 199, 999.
+    let mut stop = min / power * power + power - 1;
+    while min <= stop && stop < max {
+        stops.push(stop);
+        power *= 10;
+        stop = min / power * power + power - 1;
+    }
+
+    // Clear the trailing digits of max with zeros: 456 => 449, 399.
+    power = 10;
+    stop = (max + 1) / power * power;
+    while min < stop && stop <= max {
+        stops.push(stop - 1);
+        power *= 10;
+        stop = (max + 1) / power * power;
+    }
+
+    stops.sort_unstable();
+    stops.dedup();
+
+    let mut start = min;
+    stops.into_iter()
+        .map(|stop| {
+            let range = (start, stop);
+            start = stop + 1;
+            range
+        })
+        .collect()
+}
+
+/**
+ * Get a pattern for a range produced by `split_range`.
+ *
+ * @param  {u64} lo
+ * @param  {u64} hi
+ * @return {String}
+ */
+fn digits_range (lo: u64, hi: u64) -> String {
+    lo.to_string().chars().zip(hi.to_string().chars())
+        .map(|(l, h)| {
+            if l == h {
+                l.to_string()
+            } else if l == '0' && h == '9' {
+                String::from(r"\d")
+            } else {
+                format!("[{}-{}]", l, h)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,174 @@
+use fancy_regex::Regex as FancyRegex;
+
+use super::{Container, Match, Options, PathError, TokenCapture, parse, to_regexp, token_captures};
+
+struct Route {
+    name: String,
+    containers: Vec<Container>,
+    regexp: FancyRegex,
+    // The path of routes without tokens, compared without the regexp.
+    literal: Option<String>,
+    options: Options
+}
+
+impl Route {
+    fn new (name: &str, pattern: &str, options: Options) -> Result<Route, PathError> {
+        let containers = parse(pattern, options.clone())?;
+        let regexp = to_regexp(&containers, options.clone())?;
+        let literal = literal_route(&containers, &options);
+
+        Ok(Route {
+            name: String::from(name),
+            containers,
+            regexp,
+            literal,
+            options
+        })
+    }
+
+    fn find (&self, text: &str) -> Option<(&str, Vec<Match>)> {
+        if let Some(ref literal) = self.literal {
+            return if matches_literal(text, literal, &self.options) {
+                Some((self.name.as_str(), vec![]))
+            } else {
+                None
+            };
+        }
+
+        token_captures(text, &self.regexp, &self.containers).map(|captures| {
+            let matches = captures.into_iter()
+                .map(TokenCapture::into_match)
+                .collect();
+
+            (self.name.as_str(), matches)
+        })
+    }
+}
+
+/**
+ * A list of named routes, matched in the order they were added, and an
+ * optional fallback route tried after all of them.
+ */
+pub struct Router {
+    default_options: Options,
+    routes: Vec<Route>,
+    fallback: Option<Route>
+}
+
+impl Router {
+    /**
+     * Create a router whose routes are compiled with `default_options`
+     * unless they are added with options of their own.
+     *
+     * @param  {Options} default_options
+     * @return {Router}
+     */
+    pub fn new (default_options: Options) -> Router {
+        Router {
+            default_options,
+            routes: vec![],
+            fallback: None
+        }
+    }
+
+    /**
+     * Add a route compiled with the default options of the router.
+     *
+     * @param  {&str} name
+     * @param  {&str} pattern
+     * @return {Result<(), PathError>}
+     */
+    pub fn add (&mut self, name: &str, pattern: &str) -> Result<(), PathError> {
+        let options = self.default_options.clone();
+
+        self.add_with(name, pattern, options)
+    }
+
+    /**
+     * Add a route compiled with `options` instead of the default options
+     * of the router.
+     *
+     * @param  {&str} name
+     * @param  {&str} pattern
+     * @param  {Options} options
+     * @return {Result<(), PathError>}
+     */
+    pub fn add_with (&mut self, name: &str, pattern: &str, options: Options) -> Result<(), PathError> {
+        self.routes.push(Route::new(name, pattern, options)?);
+
+        Ok(())
+    }
+
+    /**
+     * Set the route compiled with the default options of the router which
+     * is only tried when no other route matches, e.g. a catch-all `(.*)`
+     * for 404 pages, so it never shadows routes added after it.
+     *
+     * @param  {&str} name
+     * @param  {&str} pattern
+     * @return {Result<(), PathError>}
+     */
+    pub fn set_fallback (&mut self, name: &str, pattern: &str) -> Result<(), PathError> {
+        self.fallback = Some(Route::new(name, pattern, self.default_options.clone())?);
+
+        Ok(())
+    }
+
+    /**
+     * Find the first route matching the text, or the fallback route when
+     * none does.
+     *
+     * @param  {&str} text
+     * @return {Option<(&str, Vec<Match>)>}
+     */
+    pub fn find (&self, text: &str) -> Option<(&str, Vec<Match>)> {
+        self.routes.iter()
+            .find_map(|route| route.find(text))
+            .or_else(|| self.fallback.as_ref().and_then(|route| route.find(text)))
+    }
+}
+
+/**
+ * Get the path of containers which can be matched by comparing strings,
+ * as they have no tokens and the options only anchor them to the text.
+ *
+ * @param  {&[Container]} containers
+ * @param  {&Options} options
+ * @return {Option<String>}
+ */
+fn literal_route (containers: &[Container], options: &Options) -> Option<String> {
+    let anchored = options.start && options.end && options.ends_with.is_empty()
+        && !options.lenient_start && !options.boundary && !options.multi_line;
+
+    if !anchored || containers.is_empty() || containers.iter().any(|container| container.token().is_some()) {
+        return None;
+    }
+
+    Some(containers.iter().map(Container::path).collect())
+}
+
+/**
+ * Compare a text with a literal route like its regexp would, folding the
+ * case of both with the `fold` option when given.
+ *
+ * @param  {&str} text
+ * @param  {&str} literal
+ * @param  {&Options} options
+ * @return {bool}
+ */
+fn matches_literal (text: &str, literal: &str, options: &Options) -> bool {
+    let text = if !options.strict && text.len() > literal.len() {
+        text.strip_suffix(options.delimiter.as_str()).unwrap_or(text)
+    } else {
+        text
+    };
+
+    if options.sensitive {
+        return text == literal;
+    }
+
+    match options.fold {
+        Some(ref fold) => text.chars().map(|c| fold(c)).eq(literal.chars().map(|c| fold(c))),
+        None => text.chars().flat_map(char::to_lowercase).eq(literal.chars().flat_map(char::to_lowercase))
+    }
+}